@@ -3,11 +3,40 @@ use std::process;
 
 use structopt::StructOpt;
 
+use pomodoro::control::Request;
+use pomodoro::{client, daemon, stats, Cli, ClientError, PomodoroConfig, Subcommand};
+
 fn main() {
-    let config = pomodoro::PomodoroConfig::from_args();
+    let cli = Cli::from_args();
+
+    let result = match cli.command {
+        None if cli.daemon => PomodoroConfig::load(&cli.options)
+            .map_err(Into::into)
+            .and_then(daemon::run),
+        None => PomodoroConfig::load(&cli.options)
+            .map_err(Into::into)
+            .and_then(pomodoro::run),
+        Some(Subcommand::Stats(options)) => stats::run(&options),
+        Some(Subcommand::Status(options)) => client::status(&options),
+        Some(Subcommand::Start(options)) => client::start(&options),
+        Some(Subcommand::Pause) => client::run(Request::Pause),
+        Some(Subcommand::Resume) => client::run(Request::Resume),
+        Some(Subcommand::Reset) => client::run(Request::Reset),
+        Some(Subcommand::Skip) => client::run(Request::Skip),
+        Some(Subcommand::Extend) => client::run(Request::Extend),
+        Some(Subcommand::Quit) => client::run(Request::Quit),
+    };
 
-    if let Err(e) = pomodoro::run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    if let Err(e) = result {
+        match e.downcast_ref::<ClientError>() {
+            Some(e) => {
+                eprintln!("{}", e);
+                process::exit(e.exit_code());
+            }
+            None => {
+                eprintln!("Application error: {}", e);
+                process::exit(1);
+            }
+        }
     }
 }
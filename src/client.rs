@@ -0,0 +1,224 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use structopt::StructOpt;
+
+use crate::config::PomodoroConfig;
+use crate::control::{self, Request, Status};
+use crate::format::{Format, Waybar};
+use crate::PomodoroOptions;
+
+/// How often `pomodoro status --follow` prints a new line.
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Flags for `pomodoro status`.
+#[derive(StructOpt, Debug)]
+pub struct StatusOptions {
+    #[structopt(long = "json", raw(conflicts_with_all = r#"&["format", "waybar"]"#))]
+    /// Prints the status as JSON instead of a line of text.
+    pub json: bool,
+
+    #[structopt(long = "format", parse(try_from_str))]
+    /// Lays the status out on one line, e.g. '{phase} {remaining}'. Placeholders: {phase},
+    /// {state}, {remaining}, {order}, {cycle_length} and {percentage}.
+    pub format: Option<Format>,
+
+    #[structopt(long = "waybar")]
+    /// Prints JSON for a waybar custom module (text, tooltip, class and percentage). The text is
+    /// laid out with --format.
+    pub waybar: bool,
+
+    #[structopt(long = "follow")]
+    /// Keeps printing the status every second, for status bars that read a stream of lines. When
+    /// no timer is running an empty line is printed instead of giving up.
+    pub follow: bool,
+}
+
+/// Why a client subcommand didn't get its way.  Each kind exits with its own status code, so
+/// scripts can tell them apart.
+#[derive(Debug)]
+pub enum ClientError {
+    /// Nothing is listening on the control socket.
+    NotRunning(PathBuf),
+    /// The timer is running, but said no.
+    Refused {
+        error: String,
+        status: Option<Status>,
+    },
+    /// Talking to the timer went wrong part way through.
+    Io(io::Error),
+}
+
+impl ClientError {
+    /// The status code the binary exits with: 2 when no timer is running, 3 when the timer
+    /// refused the request, and 1 for anything else.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ClientError::NotRunning(_) => 2,
+            ClientError::Refused { .. } => 3,
+            ClientError::Io(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::NotRunning(path) => write!(
+                f,
+                "no pomodoro is running (nothing is listening on {})",
+                path.display()
+            ),
+            ClientError::Refused {
+                error,
+                status: Some(status),
+            } => write!(f, "{} - {}", error, status),
+            ClientError::Refused { error, .. } => write!(f, "{}", error),
+            ClientError::Io(error) => write!(f, "couldn't talk to the running pomodoro: {}", error),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+/// Sends `request` to the running timer, and hands back where the timer is at afterwards.
+pub fn send(request: Request) -> Result<Status, ClientError> {
+    let path = control::socket_path().map_err(ClientError::Io)?;
+    send_to(&path, request)
+}
+
+/// Sends `request` to the timer listening on `path`.
+pub fn send_to(path: &Path, request: Request) -> Result<Status, ClientError> {
+    let response = match control::send(path, request) {
+        Ok(response) => response,
+        Err(ref e)
+            if e.kind() == io::ErrorKind::NotFound
+                || e.kind() == io::ErrorKind::ConnectionRefused =>
+        {
+            return Err(ClientError::NotRunning(path.to_path_buf()))
+        }
+        Err(e) => return Err(ClientError::Io(e)),
+    };
+
+    match (response.ok, response.status) {
+        (true, Some(status)) => Ok(status),
+        (true, None) => Err(ClientError::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the running pomodoro didn't send back its status",
+        ))),
+        (false, status) => Err(ClientError::Refused {
+            error: response.error.unwrap_or_default(),
+            status,
+        }),
+    }
+}
+
+/// Runs one of the subcommands that just pass a request on (`pomodoro pause`, `pomodoro skip`
+/// and friends), and prints where the timer is at afterwards.
+pub fn run(request: Request) -> Result<(), Box<dyn Error>> {
+    println!("{}", send(request)?);
+    Ok(())
+}
+
+/// Runs `pomodoro status`.
+pub fn status(options: &StatusOptions) -> Result<(), Box<dyn Error>> {
+    let stdout = io::stdout();
+    loop {
+        let line = match send(Request::Status) {
+            Ok(status) => status_line(&status, options)?,
+            Err(ClientError::NotRunning(_)) if options.follow && options.waybar => {
+                Waybar::stopped().to_string()
+            }
+            Err(ClientError::NotRunning(_)) if options.follow => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut stdout = stdout.lock();
+        writeln!(stdout, "{}", line)?;
+        stdout.flush()?;
+
+        if !options.follow {
+            return Ok(());
+        }
+        thread::sleep(FOLLOW_INTERVAL);
+    }
+}
+
+fn status_line(status: &Status, options: &StatusOptions) -> Result<String, Box<dyn Error>> {
+    let line = if options.waybar {
+        Waybar::new(status, &options.format.clone().unwrap_or_default()).to_string()
+    } else if let Some(format) = &options.format {
+        format.render(status)
+    } else if options.json {
+        serde_json::to_string(status)?
+    } else {
+        status.to_string()
+    };
+
+    Ok(line)
+}
+
+/// Runs `pomodoro start`.  With no flags the running timer keeps its own settings; otherwise the
+/// settings are worked out here (config file, profile and flags, just like starting a new timer)
+/// and sent along.
+pub fn start(options: &PomodoroOptions) -> Result<(), Box<dyn Error>> {
+    let config = if *options == PomodoroOptions::default() {
+        None
+    } else {
+        Some(Box::new(PomodoroConfig::load(options)?))
+    };
+
+    run(Request::Start { config })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{ControlServer, Response};
+    use crate::state::PomodoroState;
+    use std::{env, process, thread};
+
+    #[test]
+    fn test_exit_codes() {
+        let path = env::temp_dir()
+            .join(format!("pomodoro-test-{}", process::id()))
+            .join("client.sock");
+
+        let error = send_to(&path, Request::Pause).unwrap_err();
+        assert_eq!(error.exit_code(), 2);
+        assert!(error.to_string().starts_with("no pomodoro is running"));
+
+        let mut server = ControlServer::bind(&path).unwrap();
+        let client_path = path.clone();
+        let client = thread::spawn(move || send_to(&client_path, Request::Resume));
+        let incoming = loop {
+            if let Some(incoming) = server.accept().unwrap() {
+                break incoming;
+            }
+        };
+        incoming
+            .respond(&Response::error("no pomodoro is running", None))
+            .unwrap();
+
+        let error = client.join().unwrap().unwrap_err();
+        assert_eq!(error.exit_code(), 3);
+        assert_eq!(error.to_string(), "no pomodoro is running");
+
+        let status = Status {
+            state: PomodoroState::ShortBreak,
+            paused: false,
+            overtime: false,
+            remaining: "03:10".to_string(),
+            remaining_secs: 190,
+            duration_secs: 300,
+            order: 2,
+            cycle_length: 4,
+        };
+        assert_eq!(status.to_string(), "Short break 03:10 (2 of 4)");
+    }
+}
@@ -0,0 +1,145 @@
+/// A simple clock struct that displays hours, minutes and seconds, and has methods for drawing a
+/// nice border around the current dispalyed time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Clock {
+    hours: u64,
+    minutes: u64,
+    seconds: u64,
+}
+
+impl Clock {
+    /// Instantiates a new clock at 00:00.
+    pub fn new() -> Clock {
+        Clock {
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+
+    /// Sets clock time in absolute milliseconds.
+    pub fn set_time_ms(&mut self, ms: u64) {
+        self.hours = ms / (1000 * 60 * 60);
+        self.minutes = (ms / (1000 * 60)) % 60;
+        self.seconds = (ms / 1000) % 60;
+    }
+
+    /// Sets clock time in absolute minutes.
+    pub fn set_time_minutes(&mut self, minutes: u64) {
+        self.set_time_ms(minutes * 60000);
+    }
+
+    /// Decrements the clock by one full second.
+    pub fn decrement_one_second(&mut self) {
+        let mut time_in_ms = self.get_ms_from_time();
+        time_in_ms -= 1000;
+        self.set_time_ms(time_in_ms);
+    }
+
+    /// Increments the clock by one full second, for a clock counting up.
+    pub fn increment_one_second(&mut self) {
+        let time_in_ms = self.get_ms_from_time();
+        self.set_time_ms(time_in_ms + 1000);
+    }
+
+    /// Translate the current clock time back into milliseconds
+    pub fn get_ms_from_time(&self) -> u64 {
+        (self.hours * 3_600_000) + (self.minutes * 60000) + (self.seconds * 1000)
+    }
+
+    /// Translates the clock struct into a human readable string. Hours are only shown once the
+    /// clock is at an hour or more.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut clock = pomodoro::Clock::new();
+    ///
+    /// clock.set_time_minutes(5);
+    /// assert_eq!(clock.get_time(), "05:00".to_string());
+    ///
+    /// clock.set_time_minutes(90);
+    /// assert_eq!(clock.get_time(), "1:30:00".to_string());
+    /// ```
+    pub fn get_time(&self) -> String {
+        if self.hours > 0 {
+            format!("{}:{:02}:{:02}", self.hours, self.minutes, self.seconds)
+        } else {
+            format!("{:02}:{:02}", self.minutes, self.seconds)
+        }
+    }
+
+    /// Given a message ("Get to Work", "Time to Chill", or the phase's own message from the
+    /// sequence) this will generate a nicely displayed clock with the message added.
+    pub fn gen_clock(&self, message: &str) -> String {
+        Clock::frame(message, &self.get_time())
+    }
+
+    /// The same clock for a phase that has run over, with the time counting up - e.g.
+    /// "+03:12 over".
+    pub fn gen_overtime_clock(&self, message: &str) -> String {
+        Clock::frame(message, &format!("+{} over", self.get_time()))
+    }
+
+    fn frame(message: &str, time: &str) -> String {
+        format!(
+            "
+╭───────────────────────────────────────╮
+│                                       │
+│{:^39}│
+│{:^39}│
+│                                       │
+╰───────────────────────────────────────╯
+",
+            message, time
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_ms() {
+        let mut clock = Clock::new();
+        clock.set_time_ms(60000);
+        assert_eq!(clock.get_time(), "01:00");
+    }
+
+    #[test]
+    fn test_clock_minutes() {
+        let mut clock = Clock::new();
+        clock.set_time_minutes(1);
+        assert_eq!(clock.get_time(), "01:00");
+    }
+
+    #[test]
+    fn test_clock_hours() {
+        let mut clock = Clock::new();
+        clock.set_time_minutes(90);
+        assert_eq!(clock.get_time(), "1:30:00");
+        assert_eq!(clock.get_ms_from_time(), 90 * 60_000);
+
+        clock.decrement_one_second();
+        assert_eq!(clock.get_time(), "1:29:59");
+    }
+
+    #[test]
+    fn test_clock_counts_up() {
+        let mut clock = Clock::new();
+        clock.set_time_ms(59 * 60_000 + 59_000);
+        clock.increment_one_second();
+        assert_eq!(clock.get_time(), "1:00:00");
+    }
+
+    #[test]
+    fn test_clock_sixty_minutes() {
+        let mut clock = Clock::new();
+        clock.set_time_minutes(60);
+        assert_eq!(clock.get_time(), "1:00:00");
+
+        clock.decrement_one_second();
+        assert_eq!(clock.get_time(), "59:59");
+    }
+}
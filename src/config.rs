@@ -0,0 +1,802 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::hooks::Hooks;
+use crate::notify::NotifySettings;
+use crate::sequence::Sequence;
+use crate::state::DEFAULT_CYCLE_LENGTH;
+use crate::{xdg, PomodoroOptions};
+
+/// Longest period (in minutes) the clock will accept - a full day.
+pub const MAX_MINUTES: u64 = 24 * 60;
+
+/// Most pomodoros a cycle can have before its long break.
+pub const MAX_CYCLE_LENGTH: u32 = 100;
+
+/// Name of the config file inside the pomodoro config directory.
+const CONFIG_FILE: &str = "config.toml";
+
+/// The settings a pomodoro session runs with, once the built in defaults, the config file, the
+/// chosen profile and any terminal flags have all been layered on top of each other (in that
+/// order - flags always win).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PomodoroConfig {
+    /// Length of a work period in minutes.
+    #[serde(deserialize_with = "checked_minutes")]
+    pub work_time: u64,
+    /// Length of a short break in minutes.
+    #[serde(deserialize_with = "checked_minutes")]
+    pub short_break_time: u64,
+    /// Length of a long break in minutes.
+    #[serde(deserialize_with = "checked_minutes")]
+    pub long_break_time: u64,
+    /// Whether phases have the fixed lengths above, or work counts up Flowtime style.
+    pub schedule: Schedule,
+    /// How breaks are worked out from the time worked, on the Flowtime schedule.
+    pub flowtime: Flowtime,
+    /// How many pomodoros you work through before a long break.
+    #[serde(deserialize_with = "checked_cycle_length")]
+    pub cycle_length: u32,
+    /// Phases to work through in place of the cycle the four settings above make up.
+    pub sequence: Option<Sequence>,
+    /// Whether the next pomodoro starts on its own once a break is over.
+    pub auto_start_work: bool,
+    /// Whether a break starts on its own once a work period is over.
+    pub auto_start_break: bool,
+    /// Seconds to count down (giving you the chance to hold) before a phase starts on its own.
+    pub grace: u64,
+    /// Whether a phase that runs down keeps counting up until you say you're done with it.
+    pub overtime: bool,
+    /// Commands to run as the timer moves between phases.
+    pub hooks: Hooks,
+    /// Where notifications go when a phase runs out.
+    pub notify: NotifySettings,
+    /// When to warn that a phase is nearly over.
+    pub warnings: Warnings,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> PomodoroConfig {
+        PomodoroConfig {
+            work_time: 25,
+            short_break_time: 5,
+            long_break_time: 20,
+            schedule: Schedule::default(),
+            flowtime: Flowtime::default(),
+            cycle_length: DEFAULT_CYCLE_LENGTH,
+            sequence: None,
+            auto_start_work: false,
+            auto_start_break: true,
+            grace: 0,
+            overtime: false,
+            hooks: Hooks::default(),
+            notify: NotifySettings::default(),
+            warnings: Warnings::default(),
+        }
+    }
+}
+
+impl PomodoroConfig {
+    /// Builds the config for a run: reads the config file (the one passed with `--config`, or
+    /// `$XDG_CONFIG_HOME/pomodoro/config.toml` if there is one), applies the `--profile` picked
+    /// from it, and then whatever flags were passed.
+    pub fn load(options: &PomodoroOptions) -> Result<PomodoroConfig, ConfigError> {
+        let file = match &options.config_file {
+            Some(path) => ConfigFile::read(path)?,
+            None => match default_path() {
+                Some(ref path) if path.exists() => ConfigFile::read(path)?,
+                _ => ConfigFile::default(),
+            },
+        };
+
+        PomodoroConfig::resolve(&file, options)
+    }
+
+    /// Layers `file`, the profile named in `options` and the flags in `options` over the
+    /// defaults.
+    pub fn resolve(
+        file: &ConfigFile,
+        options: &PomodoroOptions,
+    ) -> Result<PomodoroConfig, ConfigError> {
+        let mut config = PomodoroConfig {
+            hooks: file.hooks.clone(),
+            notify: file.notify.clone(),
+            warnings: file.warnings.clone(),
+            flowtime: file.flowtime.clone(),
+            ..PomodoroConfig::default()
+        };
+        config.apply(&file.settings);
+
+        if let Some(name) = &options.profile {
+            let profile = file
+                .profiles
+                .get(name)
+                .ok_or_else(|| ConfigError::UnknownProfile {
+                    name: name.clone(),
+                    known: file.profiles.keys().cloned().collect(),
+                })?;
+            config.apply(profile);
+        }
+
+        config.apply(&Settings {
+            work: options.work_time,
+            short_break: options.short_break_time,
+            long_break: options.long_break_time,
+            schedule: options.schedule,
+            cycle_length: options.cycle_length,
+            sequence: options.sequence.clone(),
+            auto_start_work: flag(options.auto_start_work, options.no_auto_start_work),
+            auto_start_break: flag(options.auto_start_break, options.no_auto_start_break),
+            grace: options.grace,
+            overtime: flag(options.overtime, options.no_overtime),
+        });
+
+        Ok(config)
+    }
+
+    /// The phases to work through - the sequence, if one is set, or else the classic cycle.
+    pub fn cycle(&self) -> Sequence {
+        match &self.sequence {
+            Some(sequence) => sequence.clone(),
+            None => Sequence::classic(
+                self.work_time,
+                self.short_break_time,
+                self.long_break_time,
+                self.cycle_length,
+            ),
+        }
+    }
+
+    fn apply(&mut self, settings: &Settings) {
+        if let Some(work) = settings.work {
+            self.work_time = work;
+        }
+        if let Some(short_break) = settings.short_break {
+            self.short_break_time = short_break;
+        }
+        if let Some(long_break) = settings.long_break {
+            self.long_break_time = long_break;
+        }
+        if let Some(schedule) = settings.schedule {
+            self.schedule = schedule;
+        }
+        if let Some(cycle_length) = settings.cycle_length {
+            self.cycle_length = cycle_length;
+        }
+        if settings.sequence.is_some() {
+            self.sequence = settings.sequence.clone();
+        } else if settings.work.is_some()
+            || settings.short_break.is_some()
+            || settings.long_break.is_some()
+            || settings.cycle_length.is_some()
+        {
+            // lengths set on top of a sequence mean you want the classic cycle back
+            self.sequence = None;
+        }
+        if let Some(auto_start_work) = settings.auto_start_work {
+            self.auto_start_work = auto_start_work;
+        }
+        if let Some(auto_start_break) = settings.auto_start_break {
+            self.auto_start_break = auto_start_break;
+        }
+        if let Some(grace) = settings.grace {
+            self.grace = grace;
+        }
+        if let Some(overtime) = settings.overtime {
+            self.overtime = overtime;
+        }
+    }
+}
+
+/// A setting that can be switched on with one flag and off with another (structopt makes sure
+/// they're never passed together).
+fn flag(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+/// How long before the end of a phase (in minutes) to warn that it is nearly over, set in the
+/// `[warnings]` table of the config file:
+///
+/// ```toml
+/// [warnings]
+/// work = [5, 2]
+/// break = [1]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Warnings {
+    /// Warnings during work periods.
+    #[serde(deserialize_with = "minutes_list")]
+    pub work: Vec<u64>,
+    /// Warnings during breaks.
+    #[serde(rename = "break", deserialize_with = "minutes_list")]
+    pub breaks: Vec<u64>,
+}
+
+/// How the length of each phase is worked out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    /// Work periods and breaks last as long as `work_time`, `short_break_time` and
+    /// `long_break_time` say.
+    #[default]
+    Pomodoro,
+    /// Work counts up until you stop it, and the break after it is worked out from how long you
+    /// worked.
+    Flowtime,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Schedule, String> {
+        match value {
+            "pomodoro" => Ok(Schedule::Pomodoro),
+            "flowtime" => Ok(Schedule::Flowtime),
+            _ => Err(format!("'{}' isn't one of pomodoro or flowtime", value)),
+        }
+    }
+}
+
+/// How long a break follows a Flowtime work period, set in the `[flowtime]` table of the config
+/// file:
+///
+/// ```toml
+/// [flowtime]
+/// ratio = 5
+/// bands = [
+///     { up_to = 25, break = 5 },
+///     { up_to = 50, break = 8 },
+///     { up_to = 90, break = 10 },
+/// ]
+/// ```
+///
+/// Work gets the break of the first band it fits in, and anything longer than every band gets one
+/// minute of break for every `ratio` minutes worked.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Flowtime {
+    /// Minutes of work for every minute of break [default: 5].
+    #[serde(deserialize_with = "ratio")]
+    pub ratio: u32,
+    /// Set breaks for work up to a given length, shortest first.
+    #[serde(deserialize_with = "bands")]
+    pub bands: Vec<Band>,
+}
+
+impl Default for Flowtime {
+    fn default() -> Flowtime {
+        Flowtime {
+            ratio: 5,
+            bands: vec![],
+        }
+    }
+}
+
+impl Flowtime {
+    /// The break earned by `worked`.
+    pub fn break_after(&self, worked: Duration) -> Duration {
+        match self
+            .bands
+            .iter()
+            .find(|band| worked <= Duration::from_secs(band.up_to * 60))
+        {
+            Some(band) => Duration::from_secs(band.break_time * 60),
+            None => Duration::from_secs(worked.as_secs() / u64::from(self.ratio)),
+        }
+    }
+}
+
+/// Work up to `up_to` minutes earns a `break` of so many minutes.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Band {
+    pub up_to: u64,
+    #[serde(rename = "break")]
+    pub break_time: u64,
+}
+
+/// One layer of settings - the top of the config file, or one of its profiles.  Anything left
+/// out falls through to the layer underneath.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    #[serde(default, deserialize_with = "minutes")]
+    pub work: Option<u64>,
+    #[serde(default, deserialize_with = "minutes")]
+    pub short_break: Option<u64>,
+    #[serde(default, deserialize_with = "minutes")]
+    pub long_break: Option<u64>,
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    #[serde(default, deserialize_with = "cycle_length")]
+    pub cycle_length: Option<u32>,
+    #[serde(default)]
+    pub sequence: Option<Sequence>,
+    #[serde(default)]
+    pub auto_start_work: Option<bool>,
+    #[serde(default)]
+    pub auto_start_break: Option<bool>,
+    /// Seconds, not minutes.
+    #[serde(default)]
+    pub grace: Option<u64>,
+    #[serde(default)]
+    pub overtime: Option<bool>,
+}
+
+/// The config file, e.g.
+///
+/// ```toml
+/// work = 30
+/// short_break = 10
+///
+/// [profiles.deep]
+/// work = 90
+/// long_break = 30
+/// cycle_length = 3
+///
+/// [hooks]
+/// on_work_start = "dnd on"
+///
+/// [notify]
+/// backends = ["desktop", "bell"]
+///
+/// [warnings]
+/// work = [2]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigFile {
+    /// Settings used whatever profile is picked.
+    pub settings: Settings,
+    /// Commands to run as the timer moves between phases, whatever profile is picked.
+    pub hooks: Hooks,
+    /// Where notifications go, whatever profile is picked.
+    pub notify: NotifySettings,
+    /// When to warn that a phase is nearly over, whatever profile is picked.
+    pub warnings: Warnings,
+    /// How Flowtime breaks are worked out, whatever profile is picked.
+    pub flowtime: Flowtime,
+    /// Named sets of settings picked with `--profile`.
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+/// How the config file is laid out on disk - the top level settings sit right next to the
+/// profiles table.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfigFile {
+    #[serde(default, deserialize_with = "minutes")]
+    work: Option<u64>,
+    #[serde(default, deserialize_with = "minutes")]
+    short_break: Option<u64>,
+    #[serde(default, deserialize_with = "minutes")]
+    long_break: Option<u64>,
+    #[serde(default)]
+    schedule: Option<Schedule>,
+    #[serde(default, deserialize_with = "cycle_length")]
+    cycle_length: Option<u32>,
+    #[serde(default)]
+    sequence: Option<Sequence>,
+    #[serde(default)]
+    auto_start_work: Option<bool>,
+    #[serde(default)]
+    auto_start_break: Option<bool>,
+    #[serde(default)]
+    grace: Option<u64>,
+    #[serde(default)]
+    overtime: Option<bool>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Settings>,
+    #[serde(default)]
+    hooks: Hooks,
+    #[serde(default)]
+    notify: NotifySettings,
+    #[serde(default)]
+    warnings: Warnings,
+    #[serde(default)]
+    flowtime: Flowtime,
+}
+
+impl ConfigFile {
+    /// Reads and checks the config file at `path`.
+    pub fn read(path: &Path) -> Result<ConfigFile, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|error| ConfigError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        ConfigFile::parse(&contents).map_err(|error| ConfigError::Parse {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    /// Parses and checks the contents of a config file.
+    pub fn parse(contents: &str) -> Result<ConfigFile, toml::de::Error> {
+        let raw: RawConfigFile = toml::from_str(contents)?;
+        raw.notify.check().map_err(serde::de::Error::custom)?;
+
+        Ok(ConfigFile {
+            settings: Settings {
+                work: raw.work,
+                short_break: raw.short_break,
+                long_break: raw.long_break,
+                schedule: raw.schedule,
+                cycle_length: raw.cycle_length,
+                sequence: raw.sequence,
+                auto_start_work: raw.auto_start_work,
+                auto_start_break: raw.auto_start_break,
+                grace: raw.grace,
+                overtime: raw.overtime,
+            },
+            profiles: raw.profiles,
+            hooks: raw.hooks,
+            notify: raw.notify,
+            warnings: raw.warnings,
+            flowtime: raw.flowtime,
+        })
+    }
+}
+
+/// Where the config file is looked for when `--config` isn't passed.
+pub fn default_path() -> Option<PathBuf> {
+    xdg::config_dir().map(|dir| dir.join(CONFIG_FILE))
+}
+
+/// Checks a period length in minutes, rejecting anything the clock can't count down from.
+pub fn check_minutes(minutes: u64) -> Result<u64, String> {
+    match minutes {
+        0 => Err("a period has to be at least 1 minute long".to_string()),
+        m if m > MAX_MINUTES => Err(format!(
+            "{} minutes is longer than a day (max is {} minutes)",
+            m, MAX_MINUTES
+        )),
+        m => Ok(m),
+    }
+}
+
+/// Checks the number of pomodoros in a cycle - a cycle needs at least one pomodoro in it, and no
+/// more than a hundred.
+pub fn check_cycle_length(length: u32) -> Result<u32, String> {
+    match length {
+        0 => Err("a cycle needs at least 1 pomodoro".to_string()),
+        l if l > MAX_CYCLE_LENGTH => Err(format!(
+            "{} pomodoros is too many for one cycle (max is {})",
+            l, MAX_CYCLE_LENGTH
+        )),
+        length => Ok(length),
+    }
+}
+
+fn minutes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    checked_minutes(deserializer).map(Some)
+}
+
+fn checked_minutes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let minutes = u64::deserialize(deserializer)?;
+    check_minutes(minutes).map_err(serde::de::Error::custom)
+}
+
+fn minutes_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u64>, D::Error> {
+    let list = Vec::<u64>::deserialize(deserializer)?;
+    list.into_iter()
+        .map(check_minutes)
+        .collect::<Result<_, _>>()
+        .map_err(serde::de::Error::custom)
+}
+
+fn ratio<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    match u32::deserialize(deserializer)? {
+        0 => Err(serde::de::Error::custom(
+            "a Flowtime ratio has to be at least 1",
+        )),
+        ratio => Ok(ratio),
+    }
+}
+
+fn bands<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Band>, D::Error> {
+    let mut bands = Vec::<Band>::deserialize(deserializer)?;
+    for band in &bands {
+        check_minutes(band.up_to).map_err(serde::de::Error::custom)?;
+        check_minutes(band.break_time).map_err(serde::de::Error::custom)?;
+    }
+    bands.sort_by_key(|band| band.up_to);
+    Ok(bands)
+}
+
+fn cycle_length<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    checked_cycle_length(deserializer).map(Some)
+}
+
+fn checked_cycle_length<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let length = u32::deserialize(deserializer)?;
+    check_cycle_length(length).map_err(serde::de::Error::custom)
+}
+
+/// Something wrong with the config file or the profile asked for.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file couldn't be read.
+    Io { path: PathBuf, error: io::Error },
+    /// The config file isn't valid - the error points at the offending line.
+    Parse {
+        path: PathBuf,
+        error: toml::de::Error,
+    },
+    /// `--profile` named a profile the config file doesn't have.
+    UnknownProfile { name: String, known: Vec<String> },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => {
+                write!(f, "couldn't read {}: {}", path.display(), error)
+            }
+            ConfigError::Parse { path, error } => write!(f, "in {}: {}", path.display(), error),
+            ConfigError::UnknownProfile { name, known } if known.is_empty() => write!(
+                f,
+                "there's no profile called '{}' - your config file doesn't have any profiles",
+                name
+            ),
+            ConfigError::UnknownProfile { name, known } => write!(
+                f,
+                "there's no profile called '{}' (try one of: {})",
+                name,
+                known.join(", ")
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::Backend;
+    use structopt::StructOpt;
+
+    const CONFIG: &str = "
+work = 30
+short_break = 10
+
+[profiles.deep]
+work = 90
+cycle_length = 3
+
+[profiles.meeting-day]
+work = 15
+long_break = 15
+";
+
+    fn options(args: &[&str]) -> PomodoroOptions {
+        PomodoroOptions::from_iter(["pomodoro"].iter().chain(args))
+    }
+
+    #[test]
+    fn test_file_over_defaults() {
+        let file = ConfigFile::parse(CONFIG).unwrap();
+        let config = PomodoroConfig::resolve(&file, &options(&[])).unwrap();
+
+        assert_eq!(
+            config,
+            PomodoroConfig {
+                work_time: 30,
+                short_break_time: 10,
+                long_break_time: 20,
+                schedule: Schedule::Pomodoro,
+                flowtime: Flowtime::default(),
+                cycle_length: 4,
+                sequence: None,
+                auto_start_work: false,
+                auto_start_break: true,
+                grace: 0,
+                overtime: false,
+                hooks: Hooks::default(),
+                notify: NotifySettings::default(),
+                warnings: Warnings::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_auto_start() {
+        let file = ConfigFile::parse(
+            "auto_start_work = true\ngrace = 10\n[profiles.strict]\nauto_start_break = false\n",
+        )
+        .unwrap();
+        let config = PomodoroConfig::resolve(&file, &options(&["-p", "strict"])).unwrap();
+        assert!(config.auto_start_work);
+        assert!(!config.auto_start_break);
+        assert_eq!(config.grace, 10);
+
+        let config = PomodoroConfig::resolve(
+            &file,
+            &options(&["-p", "strict", "--no-auto-start-work", "--auto-start-break"]),
+        )
+        .unwrap();
+        assert!(!config.auto_start_work);
+        assert!(config.auto_start_break);
+
+        let clash = PomodoroOptions::from_iter_safe(&[
+            "pomodoro",
+            "--auto-start-work",
+            "--no-auto-start-work",
+        ]);
+        assert!(clash.is_err());
+
+        let file = ConfigFile::parse("overtime = true\n").unwrap();
+        assert!(
+            PomodoroConfig::resolve(&file, &options(&[]))
+                .unwrap()
+                .overtime
+        );
+        let config = PomodoroConfig::resolve(&file, &options(&["--no-overtime"])).unwrap();
+        assert!(!config.overtime);
+    }
+
+    #[test]
+    fn test_profile_then_flags() {
+        let file = ConfigFile::parse(CONFIG).unwrap();
+
+        let config = PomodoroConfig::resolve(&file, &options(&["--profile", "deep"])).unwrap();
+        assert_eq!(config.work_time, 90);
+        assert_eq!(config.short_break_time, 10);
+        assert_eq!(config.cycle_length, 3);
+
+        let args = ["--profile", "deep", "-w", "45", "-c", "2"];
+        let config = PomodoroConfig::resolve(&file, &options(&args)).unwrap();
+        assert_eq!(config.work_time, 45);
+        assert_eq!(config.cycle_length, 2);
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        let file = ConfigFile::parse(CONFIG).unwrap();
+        let error = PomodoroConfig::resolve(&file, &options(&["--profile", "study"])).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "there's no profile called 'study' (try one of: deep, meeting-day)"
+        );
+    }
+
+    #[test]
+    fn test_bad_values_point_at_line() {
+        let error = ConfigFile::parse("work = 25\n\n[profiles.deep]\nwork = 0\n").unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("line 4"), "{}", message);
+        assert!(message.contains("at least 1 minute"), "{}", message);
+
+        let error = ConfigFile::parse("work = 25\nshort_brake = 5\n").unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("line 2"), "{}", message);
+        assert!(message.contains("short_brake"), "{}", message);
+
+        let error = ConfigFile::parse("cycle_length = \"four\"\n").unwrap_err();
+        assert!(error.to_string().contains("line 1"));
+
+        let error = ConfigFile::parse("cycle_length = 4000000000\n").unwrap_err();
+        assert!(error.to_string().contains("max is 100"), "{}", error);
+
+        // settings sent over the control socket are held to the same limits
+        let json = r#"{"cycle_length":4000000000}"#;
+        assert!(serde_json::from_str::<PomodoroConfig>(json).is_err());
+    }
+
+    #[test]
+    fn test_hooks() {
+        let file = ConfigFile::parse(&format!(
+            "{}\n[hooks]\non_work_start = \"dnd on\"\ntimeout = 3\n",
+            CONFIG
+        ))
+        .unwrap();
+        let config = PomodoroConfig::resolve(&file, &options(&["-p", "deep"])).unwrap();
+        assert_eq!(config.hooks.on_work_start.as_deref(), Some("dnd on"));
+        assert_eq!(config.hooks.timeout(), std::time::Duration::from_secs(3));
+
+        let error = ConfigFile::parse("[hooks]\non_lunch = \"eat\"\n").unwrap_err();
+        assert!(error.to_string().contains("on_lunch"));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let file = ConfigFile::parse("[warnings]\nwork = [5, 2]\nbreak = [1]\n").unwrap();
+        let config = PomodoroConfig::resolve(&file, &options(&[])).unwrap();
+        assert_eq!(config.warnings.work, vec![5, 2]);
+        assert_eq!(config.warnings.breaks, vec![1]);
+
+        let error = ConfigFile::parse("[warnings]\nwork = [0]\n").unwrap_err();
+        assert!(error.to_string().contains("at least 1 minute"));
+    }
+
+    #[test]
+    fn test_flowtime() {
+        let file = ConfigFile::parse(
+            r#"
+[profiles.flow]
+schedule = "flowtime"
+
+[flowtime]
+ratio = 4
+bands = [
+    { up_to = 50, break = 8 },
+    { up_to = 25, break = 5 },
+]
+"#,
+        )
+        .unwrap();
+        let config = PomodoroConfig::resolve(&file, &options(&["-p", "flow"])).unwrap();
+        assert_eq!(config.schedule, Schedule::Flowtime);
+
+        let minutes = |minutes: u64| Duration::from_secs(minutes * 60);
+        assert_eq!(config.flowtime.break_after(minutes(10)), minutes(5));
+        assert_eq!(config.flowtime.break_after(minutes(25)), minutes(5));
+        assert_eq!(config.flowtime.break_after(minutes(40)), minutes(8));
+        assert_eq!(config.flowtime.break_after(minutes(100)), minutes(25));
+
+        let args = ["-p", "flow", "--schedule", "pomodoro"];
+        let config = PomodoroConfig::resolve(&file, &options(&args)).unwrap();
+        assert_eq!(config.schedule, Schedule::Pomodoro);
+
+        let error = ConfigFile::parse("[flowtime]\nratio = 0\n").unwrap_err();
+        assert!(error.to_string().contains("at least 1"));
+        assert!(PomodoroOptions::from_iter_safe(&["pomodoro", "--schedule", "52/17"]).is_err());
+    }
+
+    #[test]
+    fn test_sequence() {
+        let file = ConfigFile::parse(&format!(
+            "{}\n[profiles.long]\nsequence = \"50w 10b 50w 10b 50w 30B\"\n",
+            CONFIG
+        ))
+        .unwrap();
+        let config = PomodoroConfig::resolve(&file, &options(&[])).unwrap();
+        assert_eq!(config.cycle(), Sequence::classic(30, 10, 20, 4));
+
+        let config = PomodoroConfig::resolve(&file, &options(&["-p", "long"])).unwrap();
+        assert_eq!(config.cycle().phases().len(), 6);
+        assert_eq!(config.cycle().phases()[5].duration, 30);
+
+        // asking for the lengths on the command line gets the classic cycle back
+        let config = PomodoroConfig::resolve(&file, &options(&["-p", "long", "-w", "45"])).unwrap();
+        assert_eq!(config.cycle(), Sequence::classic(45, 10, 20, 4));
+
+        let config = PomodoroConfig::resolve(&file, &options(&["--sequence", "90w 15b"])).unwrap();
+        assert_eq!(config.cycle(), "90w 15b".parse().unwrap());
+
+        let error = ConfigFile::parse("work = 25\nsequence = \"25w 5b 25\"\n").unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("line 2"), "{}", message);
+        assert!(message.contains("'25' isn't a phase"), "{}", message);
+    }
+
+    #[test]
+    fn test_notify() {
+        let file =
+            ConfigFile::parse("[notify]\nbackends = [\"osc9\", \"command\"]\ncommand = \"ntfy\"\n")
+                .unwrap();
+        let config = PomodoroConfig::resolve(&file, &options(&[])).unwrap();
+        assert_eq!(
+            config.notify.backends,
+            vec![Backend::Osc9, Backend::Command]
+        );
+
+        let error = ConfigFile::parse("[notify]\nbackends = [\"command\"]\n").unwrap_err();
+        assert!(error.to_string().contains("needs a command"));
+    }
+}
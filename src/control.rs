@@ -0,0 +1,547 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::PomodoroConfig;
+use crate::engine::{Engine, Event, SNOOZE};
+use crate::state::PomodoroState;
+use crate::xdg;
+
+/// Name of the control socket inside the runtime directory.
+const SOCKET_FILE: &str = "pomodoro.sock";
+
+/// How long either end of the socket waits on the other before giving up.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Longest request line the timer will take, in bytes.  Even a start with a full config is well
+/// under this.
+const MAX_REQUEST: usize = 4096;
+
+/// Something a client asks the running timer to do.  Requests are sent one JSON object per line,
+/// e.g. `{"command":"pause"}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Starts the next pomodoro (or the break waiting to be started), if the timer is waiting
+    /// between phases - with new settings from here on, if `config` is given.
+    Start {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        config: Option<Box<PomodoroConfig>>,
+    },
+    /// Freezes the running clock.
+    Pause,
+    /// Picks a paused clock back up.
+    Resume,
+    /// Restarts the current pomodoro from the top of its work period.
+    Reset,
+    /// Cuts the running phase short and moves on to the next one.
+    Skip,
+    /// Gives the running phase five more minutes, or snoozes the one that has just run down.
+    Extend,
+    /// Abandons whatever is running and shuts the timer down.
+    Quit,
+    /// Just asks where the timer is at.
+    Status,
+}
+
+impl Request {
+    /// Carries the request out on `engine`, handing back whatever the engine reported - or why
+    /// the request couldn't be carried out.
+    pub fn apply(&self, engine: &mut Engine, now: Instant) -> Result<Vec<Event>, &'static str> {
+        let running = engine.is_running();
+        match self {
+            Request::Status => Ok(vec![]),
+            Request::Start { .. } if running => Err("a pomodoro is already running"),
+            Request::Start { config } => {
+                if let Some(config) = config {
+                    engine.reconfigure(config);
+                }
+                Ok(engine.start_next(now))
+            }
+            Request::Quit => Ok(engine.stop(now)),
+            Request::Extend => match engine.extend(now, SNOOZE) {
+                events if events.is_empty() => Err("no pomodoro is running"),
+                events => Ok(events),
+            },
+            _ if !running => Err("no pomodoro is running"),
+            Request::Pause => Ok(engine.pause(now)),
+            Request::Resume => Ok(engine.resume(now)),
+            Request::Reset => Ok(engine.reset(now)),
+            Request::Skip => Ok(engine.skip(now)),
+        }
+    }
+}
+
+/// Where a running timer is at.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub state: PomodoroState,
+    pub paused: bool,
+    /// Whether the phase has run down and is running over, waiting to be acknowledged.
+    #[serde(default)]
+    pub overtime: bool,
+    /// What is left on the clock, as it is displayed - e.g. "24:40", or "+03:12" once the phase
+    /// is running over.  A Flowtime work period shows the time worked so far instead, with
+    /// nothing in `remaining_secs`.
+    pub remaining: String,
+    pub remaining_secs: u64,
+    /// Full length of the current phase, in seconds.
+    pub duration_secs: u64,
+    /// Position in the cycle (0 before the first pomodoro has started).
+    pub order: u32,
+    pub cycle_length: u32,
+}
+
+impl Status {
+    /// The status of the timer `engine` is running.
+    pub fn of(engine: &Engine) -> Status {
+        let overtime = engine.is_overtime();
+        let (remaining, remaining_secs) = match overtime {
+            true => (format!("+{}", engine.clock().get_time()), 0),
+            false if engine.is_flowing() => (engine.clock().get_time(), 0),
+            false => (
+                engine.clock().get_time(),
+                engine.clock().get_ms_from_time() / 1000,
+            ),
+        };
+
+        Status {
+            state: engine.state(),
+            paused: engine.is_paused(),
+            overtime,
+            remaining,
+            remaining_secs,
+            duration_secs: engine.phase_duration().as_secs(),
+            order: engine.order(),
+            cycle_length: engine.cycle_length(),
+        }
+    }
+
+    /// The phase as a word or two - "Working", "Short break", "Long break", "Paused", "Overtime",
+    /// or "Waiting" between pomodoros.
+    pub fn phase(&self) -> &'static str {
+        match self.state {
+            PomodoroState::None => "Waiting",
+            _ if self.paused => "Paused",
+            _ if self.overtime => "Overtime",
+            PomodoroState::Working => "Working",
+            PomodoroState::ShortBreak => "Short break",
+            PomodoroState::LongBreak => "Long break",
+        }
+    }
+
+    /// How much of the current phase has gone by, from 0 to 100.
+    pub fn percentage(&self) -> u64 {
+        match self.duration_secs {
+            0 => 0,
+            duration => duration.saturating_sub(self.remaining_secs) * 100 / duration,
+        }
+    }
+}
+
+/// Shows the status the way `pomodoro status` prints it, e.g. "Working 17:42 (2 of 4)".
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.state == PomodoroState::None {
+            return write!(
+                f,
+                "Waiting between pomodoros ({} of {})",
+                self.order, self.cycle_length
+            );
+        }
+
+        write!(
+            f,
+            "{} {} ({} of {})",
+            self.phase(),
+            self.remaining,
+            self.order,
+            self.cycle_length
+        )
+    }
+}
+
+/// The timer's answer to a request, sent back as a single JSON line.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    /// Why the request couldn't be carried out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Where the timer is at once the request has been dealt with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    /// A request that went through.
+    pub fn ok(status: Status) -> Response {
+        Response {
+            ok: true,
+            error: None,
+            status: Some(status),
+        }
+    }
+
+    /// A request that couldn't be carried out.
+    pub fn error<S: Into<String>>(error: S, status: Option<Status>) -> Response {
+        Response {
+            ok: false,
+            error: Some(error.into()),
+            status,
+        }
+    }
+}
+
+/// Where the control socket lives - `$XDG_RUNTIME_DIR/pomodoro.sock`.
+pub fn socket_path() -> io::Result<PathBuf> {
+    Ok(xdg::runtime_dir()?.join(SOCKET_FILE))
+}
+
+/// The listening end of the control socket.  It never blocks waiting for clients - the timer
+/// checks in on it every time round its loop, and a request that hasn't all arrived yet is picked
+/// up where it left off next time.  The socket file is removed again when the server is dropped.
+#[derive(Debug)]
+pub struct ControlServer {
+    listener: UnixListener,
+    path: PathBuf,
+    /// Clients that have connected but not yet sent a whole request.
+    pending: Vec<Pending>,
+}
+
+/// A client part way through sending its request.
+#[derive(Debug)]
+struct Pending {
+    stream: UnixStream,
+    received: Vec<u8>,
+    connected_at: Instant,
+}
+
+impl ControlServer {
+    /// Starts listening on `path`.  Fails if another timer is already listening there; a socket
+    /// left behind by a timer that didn't shut down cleanly is cleared away.
+    pub fn bind<P: Into<PathBuf>>(path: P) -> io::Result<ControlServer> {
+        let path = path.into();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        if UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!(
+                    "another pomodoro is already listening on {}",
+                    path.display()
+                ),
+            ));
+        }
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(ControlServer {
+            listener,
+            path,
+            pending: vec![],
+        })
+    }
+
+    /// Where the socket lives.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The next request waiting on the socket, if there is one.  Clients that send something
+    /// that isn't a request, or one that's too long, are told so straight away and skipped, and
+    /// clients that take longer than a couple of seconds to send a whole request are dropped.
+    pub fn accept(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    self.pending.push(Pending {
+                        stream,
+                        received: vec![],
+                        connected_at: Instant::now(),
+                    });
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut i = 0;
+        while i < self.pending.len() {
+            // a client that hangs up, stalls or sends garbage is its own problem, not the timer's
+            match self.pending[i].read() {
+                Ok(Some(line)) => {
+                    let pending = self.pending.remove(i);
+                    if let Ok(Some(incoming)) = Incoming::parse(pending.stream, &line) {
+                        return Ok(Some(incoming));
+                    }
+                }
+                Ok(None) if self.pending[i].connected_at.elapsed() < TIMEOUT => i += 1,
+                Err(ref e) if e.kind() == io::ErrorKind::InvalidData => {
+                    let pending = self.pending.remove(i);
+                    let _ = refuse(&pending.stream, e.to_string());
+                }
+                _ => {
+                    self.pending.remove(i);
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Pending {
+    /// Reads whatever the client has sent so far, handing back the request line once it has all
+    /// arrived.  A client that hangs up before sending anything, or whose request runs past
+    /// `MAX_REQUEST`, is an error.
+    fn read(&mut self) -> io::Result<Option<String>> {
+        let mut buf = [0; 512];
+        loop {
+            let end = self.received.iter().position(|&b| b == b'\n');
+            if end.unwrap_or(self.received.len()) > MAX_REQUEST {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("requests can't be longer than {} bytes", MAX_REQUEST),
+                ));
+            }
+            if let Some(end) = end {
+                self.received.truncate(end);
+                return Ok(Some(String::from_utf8_lossy(&self.received).into_owned()));
+            }
+            match self.stream.read(&mut buf) {
+                Ok(0) if self.received.is_empty() => {
+                    return Err(io::ErrorKind::UnexpectedEof.into())
+                }
+                // hanging up counts as the end of the line
+                Ok(0) => self.received.push(b'\n'),
+                Ok(n) => self.received.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A request that has come in over the socket, waiting to be answered.
+#[derive(Debug)]
+pub struct Incoming {
+    pub request: Request,
+    stream: UnixStream,
+}
+
+impl Incoming {
+    fn parse(stream: UnixStream, line: &str) -> io::Result<Option<Incoming>> {
+        match serde_json::from_str(line) {
+            Ok(request) => {
+                // answers are small and go out in one go, so writing can block for a moment
+                stream.set_nonblocking(false)?;
+                stream.set_write_timeout(Some(TIMEOUT))?;
+                Ok(Some(Incoming { request, stream }))
+            }
+            Err(e) => {
+                refuse(&stream, format!("not a request: {}", e))?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Sends `response` back to whoever made the request.
+    pub fn respond(self, response: &Response) -> io::Result<()> {
+        write_line(&self.stream, response)
+    }
+
+    /// Lets whoever made the request know whether it went through (`error` says why not) and
+    /// where `engine` is at now.  A client that has already hung up doesn't need an answer, so
+    /// that isn't treated as an error.
+    pub fn answer(self, error: Option<&str>, engine: &Engine) {
+        let status = Status::of(engine);
+        let response = match error {
+            Some(error) => Response::error(error, Some(status)),
+            None => Response::ok(status),
+        };
+        let _ = self.respond(&response);
+    }
+}
+
+/// Tells a client why what it sent won't be carried out.
+fn refuse(stream: &UnixStream, error: String) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write_line(stream, &Response::error(error, None))
+}
+
+/// Sends `request` to the timer listening on `path` and waits for its answer.
+pub fn send(path: &Path, request: Request) -> io::Result<Response> {
+    let stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write_line(&stream, &request)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+fn write_line<T: Serialize>(mut stream: &UnixStream, value: &T) -> io::Result<()> {
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PomodoroConfig;
+    use std::env;
+    use std::process;
+    use std::thread;
+
+    fn temp_socket(name: &str) -> PathBuf {
+        env::temp_dir()
+            .join(format!("pomodoro-test-{}", process::id()))
+            .join(format!("{}.sock", name))
+    }
+
+    #[test]
+    fn test_request_json() {
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command":"skip"}"#).unwrap(),
+            Request::Skip
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"command":"explode"}"#).is_err());
+
+        // settings that come with a start are checked just as the config file's are
+        for config in [
+            r#"{"work_time":0}"#,
+            r#"{"short_break_time":18446744073709551615}"#,
+            r#"{"long_break_time":1441}"#,
+        ] {
+            let json = format!(r#"{{"command":"start","config":{}}}"#, config);
+            let error = serde_json::from_str::<Request>(&json).unwrap_err();
+            assert!(error.to_string().contains("minute"), "{}", error);
+        }
+        let json = r#"{"command":"start","config":{"work_time":50}}"#;
+        match serde_json::from_str::<Request>(json).unwrap() {
+            Request::Start { config } => assert_eq!(config.unwrap().work_time, 50),
+            other => panic!("expected a start, got {:?}", other),
+        }
+
+        let mut engine = Engine::new(&PomodoroConfig::default());
+        engine.start_work(Instant::now());
+        let json = serde_json::to_value(Response::ok(Status::of(&engine))).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "ok": true,
+                "status": {
+                    "state": "working",
+                    "paused": false,
+                    "overtime": false,
+                    "remaining": "25:00",
+                    "remaining_secs": 1500,
+                    "duration_secs": 1500,
+                    "order": 1,
+                    "cycle_length": 4,
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_socket("round-trip");
+        let mut server = ControlServer::bind(&path).unwrap();
+        assert!(ControlServer::bind(&path).is_err());
+
+        let client_path = path.clone();
+        let client = thread::spawn(move || send(&client_path, Request::Pause).unwrap());
+
+        let incoming = loop {
+            if let Some(incoming) = server.accept().unwrap() {
+                break incoming;
+            }
+            thread::sleep(Duration::from_millis(5));
+        };
+        assert_eq!(incoming.request, Request::Pause);
+        incoming
+            .respond(&Response::error("nothing is running", None))
+            .unwrap();
+
+        let response = client.join().unwrap();
+        assert!(!response.ok);
+        assert_eq!(response.error.as_deref(), Some("nothing is running"));
+
+        drop(server);
+        assert!(!path.exists());
+        assert!(send(&path, Request::Status).is_err());
+    }
+
+    #[test]
+    fn test_slow_clients_dont_block() {
+        let path = temp_socket("slow");
+        let mut server = ControlServer::bind(&path).unwrap();
+
+        // one client says nothing at all, and another sends its request a bit at a time
+        let _silent = UnixStream::connect(&path).unwrap();
+        let mut slow = UnixStream::connect(&path).unwrap();
+        slow.write_all(br#"{"command":"#).unwrap();
+
+        let started = Instant::now();
+        assert!(server.accept().unwrap().is_none());
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        slow.write_all(b"\"skip\"}\n").unwrap();
+        let incoming = loop {
+            if let Some(incoming) = server.accept().unwrap() {
+                break incoming;
+            }
+            thread::sleep(Duration::from_millis(5));
+        };
+        assert_eq!(incoming.request, Request::Skip);
+        assert_eq!(server.pending.len(), 1);
+    }
+
+    #[test]
+    fn test_long_requests_are_refused() {
+        let path = temp_socket("long");
+        let mut server = ControlServer::bind(&path).unwrap();
+
+        // a client that never gets round to a newline is cut off once it's sent too much
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(&[b' '; MAX_REQUEST + 1]).unwrap();
+        for _ in 0..100 {
+            assert!(server.accept().unwrap().is_none());
+            if server.pending.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(server.pending.is_empty());
+
+        client.set_read_timeout(Some(TIMEOUT)).unwrap();
+        let mut line = String::new();
+        BufReader::new(&client).read_line(&mut line).unwrap();
+        let response: Response = serde_json::from_str(&line).unwrap();
+        assert!(!response.ok);
+        assert!(response.error.unwrap().contains("longer than"));
+    }
+}
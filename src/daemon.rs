@@ -0,0 +1,345 @@
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Local;
+
+use crate::config::PomodoroConfig;
+use crate::control::{self, ControlServer, Request};
+use crate::engine::{Engine, Event, Outcome, SNOOZE};
+use crate::error::PomodoroError;
+use crate::history::{History, HistoryEntry};
+use crate::hooks::Hooks;
+use crate::notify::{Message, Notifier, Notifiers};
+use crate::sound::Ambience;
+use crate::time::{SystemClock, TimeSource};
+use crate::xdg;
+
+/// Name of the pidfile inside the runtime directory.
+const PID_FILE: &str = "pomodoro.pid";
+
+/// How often the daemon checks the control socket while nothing else is due.
+const POLL: Duration = Duration::from_millis(100);
+
+/// The timer without a terminal - the same work and break cycle as the terminal UI, but driven
+/// entirely over the control socket, with notifications the only thing you see.
+pub struct Daemon<T = SystemClock> {
+    engine: Engine,
+    hooks: Hooks,
+    notifier: Box<dyn Notifier>,
+    ambience: Ambience,
+    control: ControlServer,
+    time: T,
+    history: Option<History>,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Daemon {
+    /// Builds an idle daemon answering requests on `control`.
+    pub fn new(config: &PomodoroConfig, control: ControlServer) -> Daemon {
+        Daemon::with_time_source(config, control, SystemClock)
+    }
+}
+
+impl<T: TimeSource> Daemon<T> {
+    /// Builds a daemon that reads the time from (and sleeps on) `time` instead of the system
+    /// clock.
+    pub fn with_time_source(config: &PomodoroConfig, control: ControlServer, time: T) -> Daemon<T> {
+        Daemon {
+            engine: Engine::new(config),
+            hooks: config.hooks.clone(),
+            notifier: Box::new(Notifiers::from_settings(&config.notify)),
+            ambience: Ambience::new(&config.notify.sound),
+            control,
+            time,
+            history: None,
+            stop: None,
+        }
+    }
+
+    /// Writes every phase that ends from here on to `history`.
+    pub fn record_to(&mut self, history: History) {
+        self.history = Some(history);
+    }
+
+    /// Sends notifications through `notifier` instead of the backends picked in the config.
+    pub fn notify_with<N: Notifier + 'static>(&mut self, notifier: N) {
+        self.notifier = Box::new(notifier);
+    }
+
+    /// Shuts down cleanly (ending whatever is running as quit) once `stop` gets set - `run`
+    /// hooks it up to SIGTERM and SIGINT.
+    pub fn stop_on(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = Some(stop);
+    }
+
+    /// The timer engine behind the daemon.
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Runs until a client sends `quit` or the daemon is told to stop.
+    pub fn serve(&mut self) -> Result<(), PomodoroError> {
+        loop {
+            let now = self.time.now();
+            if self
+                .stop
+                .as_ref()
+                .is_some_and(|stop| stop.load(Ordering::Relaxed))
+            {
+                let events = self.engine.stop(now);
+                return self.handle_events(events);
+            }
+
+            while let Some(incoming) = self.control.accept().map_err(PomodoroError::Control)? {
+                let quit = incoming.request == Request::Quit;
+                let error = match incoming.request.apply(&mut self.engine, now) {
+                    Ok(events) => {
+                        self.handle_events(events)?;
+                        None
+                    }
+                    Err(error) => Some(error),
+                };
+                incoming.answer(error, &self.engine);
+
+                if quit {
+                    return Ok(());
+                }
+            }
+
+            if self.notifier.snoozed() {
+                let events = self.engine.snooze(now, SNOOZE);
+                self.handle_events(events)?;
+            }
+
+            let events = self.engine.tick(now);
+            self.handle_events(events)?;
+
+            let wait = match self.engine.next_tick_at(now) {
+                Some(next) => next.saturating_duration_since(now).min(POLL),
+                None => POLL,
+            };
+            self.time.sleep(wait);
+        }
+    }
+
+    /// Runs the hooks, sends the notifications before and at the end of each phase and keeps the
+    /// history up to date.  With no screen to show them on, hooks that can't be started and
+    /// notifications that can't be sent are logged to stderr.
+    fn handle_events(&mut self, events: Vec<Event>) -> Result<(), PomodoroError> {
+        for event in events {
+            if let Err(e) = self.hooks.fire(&event) {
+                eprintln!("warning: couldn't run a hook: {}", e);
+            }
+            if let Err(e) = self.ambience.follow(&event) {
+                eprintln!("warning: couldn't play the ambient sound: {}", e);
+            }
+
+            match event {
+                Event::Warning { state, remaining } => {
+                    self.notify(&Message::warning(state, remaining));
+                }
+                Event::Nudge { state, over } => self.notify(&Message::nudge(state, over)),
+                Event::PhaseEnded(summary) => {
+                    // a phase that ran over has been nudged about already
+                    if summary.outcome == Outcome::Completed && summary.overtime.is_none() {
+                        self.notify(&Message::phase_end(summary.state));
+                    }
+                    if let Some(history) = &self.history {
+                        let entry = HistoryEntry::from_summary(&summary, Local::now());
+                        history.record(&entry).map_err(PomodoroError::History)?;
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn notify(&mut self, message: &Message) {
+        if let Err(e) = self.notifier.notify(message) {
+            eprintln!("warning: {}", e);
+        }
+    }
+}
+
+/// A pidfile holding the daemon's process id, removed again when it is dropped.  Only one can be
+/// held at a time - a pidfile left behind by a daemon that has since died is cleared away.
+#[derive(Debug)]
+pub struct Pidfile {
+    path: PathBuf,
+}
+
+impl Pidfile {
+    /// Writes this process's id to `path`, unless a daemon that is still alive already has.
+    pub fn create<P: Into<PathBuf>>(path: P) -> io::Result<Pidfile> {
+        let path = path.into();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    writeln!(file, "{}", process::id())?;
+                    return Ok(Pidfile { path });
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => match read_pid(&path) {
+                    Some(pid) if is_alive(pid) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::AddrInUse,
+                            format!("a pomodoro daemon is already running (pid {})", pid),
+                        ));
+                    }
+                    _ => fs::remove_file(&path)?,
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Where the pidfile lives.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Pidfile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn read_pid(path: &Path) -> Option<i32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether a process with id `pid` exists.
+fn is_alive(pid: i32) -> bool {
+    // signal 0 sends nothing - it only checks that the process is there to be signalled
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Where the daemon's pidfile lives - `$XDG_RUNTIME_DIR/pomodoro.pid`.
+pub fn pidfile_path() -> io::Result<PathBuf> {
+    Ok(xdg::runtime_dir()?.join(PID_FILE))
+}
+
+/// Runs `pomodoro --daemon`: takes the pidfile and the control socket, then serves requests
+/// until told to quit.
+pub fn run(config: PomodoroConfig) -> Result<(), Box<dyn Error>> {
+    let pidfile = pidfile_path()
+        .and_then(Pidfile::create)
+        .map_err(PomodoroError::Daemon)?;
+    let control = control::socket_path()
+        .and_then(ControlServer::bind)
+        .map_err(PomodoroError::Control)?;
+    let history = History::open_default().map_err(PomodoroError::History)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in &[signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+        signal_hook::flag::register(*signal, Arc::clone(&stop)).map_err(PomodoroError::Daemon)?;
+    }
+
+    eprintln!(
+        "pomodoro daemon listening on {} (pid {} in {})",
+        control.path().display(),
+        process::id(),
+        pidfile.path().display()
+    );
+
+    let mut daemon = Daemon::new(&config, control);
+    daemon.record_to(history);
+    daemon.stop_on(stop);
+    daemon.serve()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::PomodoroState;
+    use crate::time::ManualClock;
+    use std::env;
+    use std::thread;
+    use std::time::Instant;
+
+    /// A fake clock that stands still while the daemon waits on it, so a period can't run out
+    /// however long a client takes to get its requests in.
+    struct Frozen(ManualClock);
+
+    impl TimeSource for Frozen {
+        fn now(&self) -> Instant {
+            self.0.now()
+        }
+
+        fn sleep(&self, _: Duration) {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir()
+            .join(format!("pomodoro-daemon-{}", process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn test_pidfile() {
+        let path = temp_path("pomodoro.pid");
+        let pidfile = Pidfile::create(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(process::id() as i32));
+
+        let error = Pidfile::create(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+
+        drop(pidfile);
+        assert!(!path.exists());
+
+        // a pidfile left behind by a process that is long gone
+        fs::write(&path, "2147483646\n").unwrap();
+        let pidfile = Pidfile::create(&path).unwrap();
+        assert_eq!(read_pid(pidfile.path()), Some(process::id() as i32));
+    }
+
+    #[test]
+    fn test_daemon_over_socket() {
+        let socket = temp_path("control.sock");
+        let history = History::at(temp_path("history.jsonl"));
+        let _ = fs::remove_file(history.path());
+
+        let config = PomodoroConfig {
+            work_time: 60,
+            ..PomodoroConfig::default()
+        };
+        let control = ControlServer::bind(&socket).unwrap();
+        let mut daemon = Daemon::with_time_source(&config, control, Frozen(ManualClock::new()));
+        daemon.record_to(history.clone());
+
+        let client = thread::spawn(move || {
+            let started = control::send(&socket, Request::Start { config: None }).unwrap();
+            let paused = control::send(&socket, Request::Pause).unwrap();
+            let quit = control::send(&socket, Request::Quit).unwrap();
+            (started, paused, quit)
+        });
+        daemon.serve().unwrap();
+
+        let (started, paused, quit) = client.join().unwrap();
+        assert_eq!(started.status.unwrap().state, PomodoroState::Working);
+        assert!(paused.status.unwrap().paused);
+        assert_eq!(quit.status.unwrap().state, PomodoroState::None);
+
+        let entries = history.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].outcome, Outcome::Quit);
+    }
+}
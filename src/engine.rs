@@ -0,0 +1,1272 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+use crate::config::{Flowtime, PomodoroConfig, Schedule, Warnings};
+use crate::state::{PomodoroState, StateTracker};
+
+/// How much time `extend` and `snooze` add on.
+pub const SNOOZE: Duration = Duration::from_secs(5 * 60);
+
+/// How soon after a phase runs down `extend` still takes it to mean that phase, rather than the
+/// one that started on its own in its place, and how long it can still be snoozed.
+const SNOOZE_WINDOW: Duration = Duration::from_secs(60);
+
+/// How often a phase that has run into overtime nudges you to move on.
+const NUDGE_EVERY: Duration = Duration::from_secs(60);
+
+/// Something that happened inside the timer.  The engine never draws, sleeps or notifies on its
+/// own - it hands these back to whoever is driving it and lets them decide what to do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A work period or break has just begun.
+    PhaseStarted {
+        state: PomodoroState,
+        order: u32,
+        duration: Duration,
+    },
+    /// The displayed clock moved on - `remaining` is what is left of the current phase.
+    Tick { remaining: Duration },
+    /// The displayed clock moved on in a Flowtime work period, which counts up - `elapsed` is
+    /// how long you have been working.
+    Elapsed { elapsed: Duration },
+    /// Counting down to `next` starting on its own, with `remaining` left to hold it.
+    Grace {
+        next: PomodoroState,
+        remaining: Duration,
+    },
+    /// The phase has run down and is running over - `over` is how far, as it is displayed.
+    Overtime {
+        state: PomodoroState,
+        over: Duration,
+    },
+    /// Time to remind you that the phase has run down - the moment it does (with nothing `over`
+    /// yet), and every minute after until the overtime is acknowledged.
+    Nudge {
+        state: PomodoroState,
+        over: Duration,
+    },
+    /// The current phase is nearly over - `remaining` is the warning time from the config that
+    /// has just been reached.
+    Warning {
+        state: PomodoroState,
+        remaining: Duration,
+    },
+    /// A phase is over, either because it ran all the way down to zero or because it was cut
+    /// short.
+    PhaseEnded(PhaseSummary),
+    /// The running phase was given `by` more time.
+    Extended { state: PomodoroState, by: Duration },
+    /// The running phase was frozen.
+    Paused,
+    /// The frozen phase picked back up.
+    Resumed,
+}
+
+/// How a phase came to an end.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The clock ran all the way down.
+    Completed,
+    /// The pomodoro was restarted from the top.
+    Reset,
+    /// The phase was abandoned and the timer went back to waiting between pomodoros.
+    Quit,
+    /// The phase was cut short to move straight on to the next one.
+    Skipped,
+}
+
+/// Everything worth knowing about a phase once it is over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhaseSummary {
+    pub state: PomodoroState,
+    pub order: u32,
+    /// How long the phase was meant to last, before any time was added on.  A Flowtime work
+    /// period lasts as long as you want it to, so it always ran for as long as was planned.
+    pub planned: Duration,
+    /// Time added on with `extend`.
+    pub extended: Duration,
+    /// Whether the phase was brought back by a snooze, to carry on the one that ran down before
+    /// it rather than being one of its own.
+    pub snoozed: bool,
+    /// How long the phase ran over, if it ran into overtime at all.  Not counted in `actual`.
+    pub overtime: Option<Duration>,
+    /// How long the clock actually ran for, not counting pauses.
+    pub actual: Duration,
+    /// How long the phase spent paused.
+    pub paused: Duration,
+    pub outcome: Outcome,
+}
+
+/// The headless pomodoro timer.  It owns the state tracker (which knows the sequence of phases,
+/// and how long each lasts) and the clock, and moves from work to break (and back to idle) as time
+/// passes.  Time only moves when the caller hands it an `Instant`, so it can be driven by the real
+/// clock, a fake one in tests, or anything in between.
+///
+/// The displayed clock is always worked out from when the phase started rather than by counting
+/// ticks, so however late a caller gets around to ticking, it never drifts from true time.
+#[derive(Debug)]
+pub struct Engine {
+    tracker: StateTracker,
+    clock: Clock,
+    schedule: Schedule,
+    flowtime: Flowtime,
+    /// Set while a Flowtime work period is counting up.
+    flowing: bool,
+    /// How long the last work period ran for, which a Flowtime break is worked out from.
+    worked: Duration,
+    phase_duration: Duration,
+    /// How much of `phase_duration` was added on after the phase started.
+    extended: Duration,
+    /// Set while a phase brought back by a snooze is running.
+    snoozed: bool,
+    /// The last phase to run down - where it sits in the sequence and when it ended - for as long
+    /// as it can still be snoozed.
+    snoozable: Option<(usize, Instant)>,
+    warnings: Warnings,
+    /// Warnings still to come in the current phase, longest first.
+    pending_warnings: Vec<Duration>,
+    auto_start_work: bool,
+    auto_start_break: bool,
+    grace: Duration,
+    overtime: bool,
+    /// Set once the running phase has run down and is counting up until it is acknowledged.
+    in_overtime: bool,
+    /// How many nudges the phase running over has given so far.
+    nudges: u32,
+    /// Set between a work period and its break, when the break doesn't follow on straight away.
+    break_next: bool,
+    /// When the next phase starts on its own, if it is going to.
+    starts_at: Option<Instant>,
+}
+
+impl Engine {
+    /// Builds an idle engine working through the sequence (or the period lengths and cycle
+    /// length) from the config.
+    pub fn new(config: &PomodoroConfig) -> Engine {
+        Engine {
+            tracker: StateTracker::with_sequence(config.cycle()),
+            clock: Clock::new(),
+            schedule: config.schedule,
+            flowtime: config.flowtime.clone(),
+            flowing: false,
+            worked: Duration::from_secs(0),
+            phase_duration: Duration::from_secs(0),
+            extended: Duration::from_secs(0),
+            snoozed: false,
+            snoozable: None,
+            warnings: config.warnings.clone(),
+            pending_warnings: vec![],
+            auto_start_work: config.auto_start_work,
+            auto_start_break: config.auto_start_break,
+            grace: Duration::from_secs(config.grace),
+            overtime: config.overtime,
+            in_overtime: false,
+            nudges: 0,
+            break_next: false,
+            starts_at: None,
+        }
+    }
+
+    /// Switches to the schedule, sequence (or period lengths and cycle length), warnings, auto
+    /// start and overtime settings from `config`.  Only takes effect from the next phase to start,
+    /// so the one running (if any) keeps its length.
+    pub fn reconfigure(&mut self, config: &PomodoroConfig) {
+        self.tracker.set_sequence(config.cycle());
+        self.schedule = config.schedule;
+        self.flowtime = config.flowtime.clone();
+        self.warnings = config.warnings.clone();
+        self.auto_start_work = config.auto_start_work;
+        self.auto_start_break = config.auto_start_break;
+        self.grace = Duration::from_secs(config.grace);
+        self.overtime = config.overtime;
+    }
+
+    /// The state tracker behind the engine.
+    pub fn tracker(&self) -> &StateTracker {
+        &self.tracker
+    }
+
+    /// The clock as it should currently be displayed.
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// The current pomodoro state.
+    pub fn state(&self) -> PomodoroState {
+        self.tracker.get_state()
+    }
+
+    /// Whether a phase is currently counting down (or paused part way through).
+    pub fn is_running(&self) -> bool {
+        self.state() != PomodoroState::None
+    }
+
+    /// Whether the running phase is paused.
+    pub fn is_paused(&self) -> bool {
+        self.tracker.is_paused()
+    }
+
+    /// Whether the running phase has run down and is counting up, waiting to be acknowledged.
+    pub fn is_overtime(&self) -> bool {
+        self.in_overtime
+    }
+
+    /// Whether a Flowtime work period is counting up, waiting for you to stop it.
+    pub fn is_flowing(&self) -> bool {
+        self.flowing
+    }
+
+    /// Whether a work period is over and its break is waiting to be started.
+    pub fn is_break_next(&self) -> bool {
+        self.break_next
+    }
+
+    /// When the next phase will start on its own, if the engine is counting down to one.
+    pub fn starts_at(&self) -> Option<Instant> {
+        self.starts_at
+    }
+
+    /// The phase `start_next` would start.
+    pub fn next_state(&self) -> PomodoroState {
+        if self.break_next {
+            self.tracker.next_break()
+        } else {
+            PomodoroState::Working
+        }
+    }
+
+    /// Full length of the current phase, including any time added on - zero for a Flowtime work
+    /// period, which has no set length.
+    pub fn phase_duration(&self) -> Duration {
+        self.phase_duration
+    }
+
+    /// Starts the next work period in the cycle - counting up, on the Flowtime schedule.
+    pub fn start_work(&mut self, now: Instant) -> Vec<Event> {
+        self.tracker.set_work_state(now);
+        let started = match self.schedule {
+            Schedule::Pomodoro => self.start_phase(self.planned()),
+            Schedule::Flowtime => self.start_phase(Duration::from_secs(0)),
+        };
+        self.flowing = self.schedule == Schedule::Flowtime;
+        vec![started]
+    }
+
+    /// Starts whatever comes next - the break waiting after a work period, or else the next work
+    /// period.
+    pub fn start_next(&mut self, now: Instant) -> Vec<Event> {
+        if self.break_next {
+            self.start_break(now)
+        } else {
+            self.start_work(now)
+        }
+    }
+
+    /// Calls off the countdown to the next phase starting on its own, leaving it to be started
+    /// by hand.  Returns whether there was a countdown to call off.
+    pub fn hold(&mut self) -> bool {
+        self.starts_at.take().is_some()
+    }
+
+    /// Restarts the current pomodoro from the top of its work period.
+    pub fn reset(&mut self, now: Instant) -> Vec<Event> {
+        let mut events = vec![];
+        if self.is_running() {
+            events.push(self.end_phase(now, Outcome::Reset));
+        }
+        self.snoozable = None;
+
+        self.tracker.decrement_cycle();
+        events.extend(self.start_work(now));
+        events
+    }
+
+    /// Abandons whatever is running and goes back to waiting between pomodoros.
+    pub fn stop(&mut self, now: Instant) -> Vec<Event> {
+        self.break_next = false;
+        self.starts_at = None;
+        self.snoozable = None;
+        if !self.is_running() {
+            return vec![];
+        }
+
+        let ended = self.end_phase(now, Outcome::Quit);
+        self.in_overtime = false;
+        self.flowing = false;
+        self.tracker.set_idle_state();
+        vec![ended]
+    }
+
+    /// Cuts the running phase short and moves on exactly as if it had run down, except that it
+    /// goes down in the history as skipped.  A phase that has already run down is acknowledged,
+    /// and a Flowtime work period - which only ever ends this way - is completed.
+    pub fn skip(&mut self, now: Instant) -> Vec<Event> {
+        if !self.is_running() {
+            return vec![];
+        }
+        if self.in_overtime {
+            return self.acknowledge(now);
+        }
+
+        let outcome = match self.flowing {
+            true => Outcome::Completed,
+            false => Outcome::Skipped,
+        };
+        self.finish_phase(now, now, outcome)
+    }
+
+    /// Adds `by` to the running phase.  Within a minute of a phase running down, whether or not
+    /// another has started in its place, it snoozes the phase that ran down instead - more time is
+    /// almost always wanted for that one.  A Flowtime work period has no end to put off, so it is
+    /// left as it is.
+    pub fn extend(&mut self, now: Instant, by: Duration) -> Vec<Event> {
+        if self.can_snooze(now) {
+            return self.snooze(now, by);
+        }
+        if !self.is_running() || self.flowing {
+            return vec![];
+        }
+
+        // a phase running over gets its time from now, not from when it ran down
+        let elapsed = Duration::from_secs(self.tracker.get_running_time(now).as_secs());
+        let by = match self.in_overtime {
+            true => elapsed + by - self.phase_duration,
+            false => by,
+        };
+        self.in_overtime = false;
+        self.phase_duration += by;
+        self.extended += by;
+        let remaining = self.phase_duration.saturating_sub(elapsed);
+        self.clock.set_time_ms(remaining.as_millis() as u64);
+        vec![Event::Extended {
+            state: self.state(),
+            by,
+        }]
+    }
+
+    /// Brings the phase that last ran down back for another `by`, cutting short (as skipped)
+    /// whatever started in its place.  That only holds for a minute after it ran down - a snooze
+    /// picked any later is too late to mean it, and does nothing.  A phase still running over is
+    /// the one its nudges offer to snooze, so it gets `by` added on instead.
+    pub fn snooze(&mut self, now: Instant, by: Duration) -> Vec<Event> {
+        if !self.can_snooze(now) {
+            self.snoozable = None;
+            return match self.in_overtime {
+                true => self.extend(now, by),
+                false => vec![],
+            };
+        }
+        let (position, _) = self.snoozable.take().unwrap();
+
+        let mut events = vec![];
+        if self.is_running() {
+            events.push(self.end_phase(now, Outcome::Skipped));
+        }
+        self.tracker.reopen(position, now);
+        events.push(self.start_phase(by));
+        self.snoozed = true;
+        events
+    }
+
+    fn can_snooze(&self, now: Instant) -> bool {
+        match self.snoozable {
+            Some((_, ended_at)) => now.saturating_duration_since(ended_at) < SNOOZE_WINDOW,
+            None => false,
+        }
+    }
+
+    /// Ends a phase that has run into overtime and moves on, as it would have done when it ran
+    /// down if overtime were off.
+    pub fn acknowledge(&mut self, now: Instant) -> Vec<Event> {
+        if !self.in_overtime {
+            return vec![];
+        }
+        self.finish_phase(now, now, Outcome::Completed)
+    }
+
+    /// Freezes the running phase.
+    pub fn pause(&mut self, now: Instant) -> Vec<Event> {
+        if !self.is_running() || self.is_paused() {
+            return vec![];
+        }
+
+        self.tracker.pause(now);
+        vec![Event::Paused]
+    }
+
+    /// Picks a paused phase back up where it left off.
+    pub fn resume(&mut self, now: Instant) -> Vec<Event> {
+        if !self.is_paused() {
+            return vec![];
+        }
+
+        self.tracker.resume(now);
+        vec![Event::Resumed]
+    }
+
+    /// Brings the clock up to date with `now`.  Finishing a work period moves on to the matching
+    /// break, and finishing a break leaves the engine idle until the next `start_work` - unless
+    /// the config says otherwise, in which case either can wait to be started by hand, or count
+    /// down the grace period and start on its own.
+    pub fn tick(&mut self, now: Instant) -> Vec<Event> {
+        if let Some(starts_at) = self.starts_at {
+            return self.tick_grace(starts_at, now);
+        }
+
+        let mut events = vec![];
+        if !self.is_running() || self.is_paused() {
+            return events;
+        }
+        if self.in_overtime {
+            return self.tick_overtime(now);
+        }
+        if self.flowing {
+            return self.tick_flow(now);
+        }
+
+        // only whole seconds are shown, so round the running time down before working out what
+        // is left on the clock
+        let elapsed = Duration::from_secs(self.tracker.get_running_time(now).as_secs());
+        let remaining = self.phase_duration.saturating_sub(elapsed);
+
+        if remaining.as_millis() as u64 != self.clock.get_ms_from_time() {
+            self.clock.set_time_ms(remaining.as_millis() as u64);
+            events.push(Event::Tick { remaining });
+        }
+
+        // a late tick can jump past more than one warning - only the nearest is worth giving
+        let mut warning = None;
+        while self
+            .pending_warnings
+            .first()
+            .is_some_and(|&at| remaining <= at && remaining > Duration::from_secs(0))
+        {
+            warning = Some(self.pending_warnings.remove(0));
+        }
+        if let Some(remaining) = warning {
+            events.push(Event::Warning {
+                state: self.state(),
+                remaining,
+            });
+        }
+
+        if remaining == Duration::from_secs(0) && self.overtime {
+            self.in_overtime = true;
+            self.nudges = 0;
+            events.extend(self.tick_overtime(now));
+        } else if remaining == Duration::from_secs(0) {
+            // the phase ends the moment it was due to, not whenever we happened to notice
+            let ended_at = self.tracker.get_started_at().unwrap() + self.phase_duration;
+            events.extend(self.finish_phase(ended_at, now, Outcome::Completed));
+        }
+
+        events
+    }
+
+    /// Counts up the time a phase that has run down is running over, nudging every minute.
+    fn tick_overtime(&mut self, now: Instant) -> Vec<Event> {
+        let elapsed = Duration::from_secs(self.tracker.get_running_time(now).as_secs());
+        let over = elapsed.saturating_sub(self.phase_duration);
+        let state = self.state();
+
+        let mut events = vec![];
+        if over.as_millis() as u64 != self.clock.get_ms_from_time() {
+            self.clock.set_time_ms(over.as_millis() as u64);
+            events.push(Event::Overtime { state, over });
+        }
+        // like warnings, a late tick that jumps past more than one nudge only gives the one
+        if over >= NUDGE_EVERY * self.nudges {
+            self.nudges = (over.as_secs() / NUDGE_EVERY.as_secs()) as u32 + 1;
+            events.push(Event::Nudge { state, over });
+        }
+        events
+    }
+
+    /// Counts up the time worked in a Flowtime work period.
+    fn tick_flow(&mut self, now: Instant) -> Vec<Event> {
+        let elapsed = Duration::from_secs(self.tracker.get_running_time(now).as_secs());
+        if elapsed.as_millis() as u64 == self.clock.get_ms_from_time() {
+            return vec![];
+        }
+        self.clock.set_time_ms(elapsed.as_millis() as u64);
+        vec![Event::Elapsed { elapsed }]
+    }
+
+    /// Ends the running phase at `ended_at` and goes idle, with a break up next if that's what
+    /// comes next in the sequence.  If that is meant to start on its own, the grace period counts
+    /// down from `ended_at`.
+    fn finish_phase(&mut self, ended_at: Instant, now: Instant, outcome: Outcome) -> Vec<Event> {
+        let finished = self.state();
+        if finished == PomodoroState::Working {
+            self.worked = self.tracker.get_running_time(ended_at);
+        }
+        let mut events = vec![self.end_phase(ended_at, outcome)];
+        self.snoozable = match outcome {
+            Outcome::Completed => self.tracker.position().map(|position| (position, ended_at)),
+            _ => None,
+        };
+        self.in_overtime = false;
+        self.flowing = false;
+        self.tracker.set_idle_state();
+
+        self.break_next = self.tracker.next_state().is_break();
+        let auto_start = match self.break_next {
+            true => self.auto_start_break,
+            false => self.auto_start_work,
+        };
+        if auto_start {
+            self.starts_at = Some(ended_at + self.grace);
+            events.extend(self.tick(now));
+        }
+        events
+    }
+
+    fn tick_grace(&mut self, starts_at: Instant, now: Instant) -> Vec<Event> {
+        if now >= starts_at {
+            self.starts_at = None;
+            return self.start_next(starts_at);
+        }
+
+        // counts down in whole seconds, showing 10s right up until it is 9s to go
+        let remaining = Duration::from_secs(seconds_up(starts_at - now));
+        if remaining.as_millis() as u64 == self.clock.get_ms_from_time() {
+            return vec![];
+        }
+        self.clock.set_time_ms(remaining.as_millis() as u64);
+        vec![Event::Grace {
+            next: self.next_state(),
+            remaining,
+        }]
+    }
+
+    /// When the clock will next need to change, if anything is counting down.
+    pub fn next_tick_at(&self, now: Instant) -> Option<Instant> {
+        if let Some(starts_at) = self.starts_at {
+            // the countdown ticks over at whole seconds before the start
+            let shown = seconds_up(starts_at.saturating_duration_since(now));
+            return Some(starts_at - Duration::from_secs(shown.saturating_sub(1)));
+        }
+        if !self.is_running() || self.is_paused() {
+            return None;
+        }
+
+        let started_at = self.tracker.get_started_at()?;
+        let elapsed = self.tracker.get_running_time(now);
+        Some(started_at + Duration::from_secs(elapsed.as_secs() + 1))
+    }
+
+    fn start_break(&mut self, now: Instant) -> Vec<Event> {
+        self.tracker.set_break_state(now);
+        match (self.state(), self.schedule) {
+            (PomodoroState::None, _) => vec![],
+            (_, Schedule::Flowtime) => {
+                vec![self.start_phase(self.flowtime.break_after(self.worked))]
+            }
+            _ => vec![self.start_phase(self.planned())],
+        }
+    }
+
+    /// How long the current phase in the sequence is meant to last.
+    fn planned(&self) -> Duration {
+        let minutes = self.tracker.phase().map_or(0, |phase| phase.duration);
+        Duration::from_secs(minutes * 60)
+    }
+
+    fn end_phase(&self, now: Instant, outcome: Outcome) -> Event {
+        let running = self.tracker.get_running_time(now);
+        let overtime = match self.in_overtime {
+            true => Some(running.saturating_sub(self.phase_duration)),
+            false => None,
+        };
+        let planned = match self.flowing {
+            true => running,
+            false => self.phase_duration - self.extended,
+        };
+
+        Event::PhaseEnded(PhaseSummary {
+            state: self.state(),
+            order: self.order(),
+            planned,
+            extended: self.extended,
+            snoozed: self.snoozed,
+            overtime,
+            actual: running - overtime.unwrap_or_default(),
+            paused: self.tracker.get_paused_time(now),
+            outcome,
+        })
+    }
+
+    fn start_phase(&mut self, duration: Duration) -> Event {
+        self.break_next = false;
+        self.starts_at = None;
+        self.phase_duration = duration;
+        self.extended = Duration::from_secs(0);
+        self.snoozed = false;
+        self.in_overtime = false;
+        self.flowing = false;
+        self.clock.set_time_ms(duration.as_millis() as u64);
+
+        // a warning as long as the phase itself would go off the moment it starts
+        let minutes = match self.state() {
+            PomodoroState::Working => &self.warnings.work,
+            _ => &self.warnings.breaks,
+        };
+        let mut pending: Vec<Duration> = minutes
+            .iter()
+            .map(|&minutes| Duration::from_secs(minutes * 60))
+            .filter(|&at| at < duration)
+            .collect();
+        pending.sort_by(|a, b| b.cmp(a));
+        pending.dedup();
+        self.pending_warnings = pending;
+
+        Event::PhaseStarted {
+            state: self.state(),
+            order: self.order(),
+            duration,
+        }
+    }
+
+    /// Where we are in the cycle - 0 before the first pomodoro has started.
+    pub fn order(&self) -> u32 {
+        self.tracker.get_order().unwrap_or(0)
+    }
+
+    /// How many pomodoros make up a cycle.
+    pub fn cycle_length(&self) -> u32 {
+        self.tracker.get_cycle_length()
+    }
+}
+
+/// `duration` in seconds, rounded up.
+fn seconds_up(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::{ManualClock, TimeSource};
+
+    fn engine(work_time: u64, short_break_time: u64) -> Engine {
+        Engine::new(&PomodoroConfig {
+            work_time,
+            short_break_time,
+            ..PomodoroConfig::default()
+        })
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_work_rolls_into_break() {
+        let start = Instant::now();
+        let mut engine = engine(1, 2);
+
+        let events = engine.start_work(start);
+        assert_eq!(
+            events,
+            vec![Event::PhaseStarted {
+                state: PomodoroState::Working,
+                order: 1,
+                duration: secs(60),
+            }]
+        );
+
+        assert_eq!(
+            engine.tick(start + Duration::from_millis(1500)),
+            vec![Event::Tick {
+                remaining: secs(59)
+            }]
+        );
+        assert_eq!(engine.clock().get_time(), "00:59");
+        assert_eq!(engine.tick(start + Duration::from_millis(1900)), vec![]);
+
+        let events = engine.tick(start + secs(61));
+        assert_eq!(
+            events,
+            vec![
+                Event::Tick { remaining: secs(0) },
+                Event::PhaseEnded(PhaseSummary {
+                    state: PomodoroState::Working,
+                    order: 1,
+                    planned: secs(60),
+                    extended: secs(0),
+                    snoozed: false,
+                    overtime: None,
+                    actual: secs(60),
+                    paused: secs(0),
+                    outcome: Outcome::Completed,
+                }),
+                Event::PhaseStarted {
+                    state: PomodoroState::ShortBreak,
+                    order: 1,
+                    duration: secs(120),
+                },
+            ]
+        );
+        assert_eq!(engine.clock().get_time(), "02:00");
+    }
+
+    /// Runs the engine against `time` until it goes idle, checking the tracker after every
+    /// event and returning each (state, order) the tracker moved through.
+    fn run_until_idle(engine: &mut Engine, time: &ManualClock) -> Vec<(PomodoroState, u32)> {
+        let mut states = vec![(engine.state(), engine.order())];
+        let mut remaining = engine.phase_duration();
+
+        while engine.is_running() {
+            for event in engine.tick(time.now()) {
+                let tracker = engine.tracker();
+                match event {
+                    Event::PhaseStarted {
+                        state,
+                        order,
+                        duration,
+                    } => {
+                        assert_eq!(tracker.get_state(), state);
+                        assert_eq!(tracker.get_order(), Some(order));
+                        assert_eq!(tracker.get_started_at(), Some(time.now()));
+                        states.push((state, order));
+                        remaining = duration;
+                    }
+                    Event::PhaseEnded(summary) => {
+                        assert_eq!(remaining, secs(0));
+                        assert_eq!(summary.outcome, Outcome::Completed);
+                        assert_eq!(summary.actual, summary.planned);
+                        assert_eq!(tracker.get_order(), Some(summary.order));
+                        assert_ne!(tracker.get_state(), summary.state);
+                    }
+                    Event::Tick { remaining: left } => {
+                        assert_eq!(left + secs(1), remaining);
+                        remaining = left;
+                    }
+                    other => panic!("unexpected event {:?}", other),
+                }
+            }
+
+            if let Some(next) = engine.next_tick_at(time.now()) {
+                time.sleep(next - time.now());
+            }
+        }
+
+        assert_eq!(engine.tracker().get_state(), PomodoroState::None);
+        assert_eq!(engine.tracker().get_started_at(), None);
+        states
+    }
+
+    #[test]
+    fn test_full_cycle_with_long_break() {
+        let time = ManualClock::new();
+        let mut engine = engine(25, 5);
+        let mut states = vec![];
+
+        for order in 1..=4 {
+            engine.start_work(time.now());
+            states.extend(run_until_idle(&mut engine, &time));
+            assert_eq!(engine.order(), order);
+        }
+
+        use PomodoroState::*;
+        assert_eq!(
+            states,
+            vec![
+                (Working, 1),
+                (ShortBreak, 1),
+                (Working, 2),
+                (ShortBreak, 2),
+                (Working, 3),
+                (ShortBreak, 3),
+                (Working, 4),
+                (LongBreak, 4),
+            ]
+        );
+
+        // four 25 minute pomodoros, three 5 minute breaks and a 20 minute long break
+        assert_eq!(time.elapsed(), secs((4 * 25 + 3 * 5 + 20) * 60));
+
+        engine.start_work(time.now());
+        assert_eq!(engine.order(), 1);
+    }
+
+    #[test]
+    fn test_pause_does_not_count() {
+        let start = Instant::now();
+        let mut engine = engine(1, 5);
+        engine.start_work(start);
+
+        assert_eq!(engine.pause(start + secs(10)), vec![Event::Paused]);
+        assert_eq!(engine.tick(start + secs(100)), vec![]);
+        assert_eq!(engine.next_tick_at(start + secs(100)), None);
+        assert_eq!(engine.resume(start + secs(110)), vec![Event::Resumed]);
+
+        engine.tick(start + secs(115));
+        assert_eq!(engine.clock().get_time(), "00:45");
+        assert_eq!(
+            engine.next_tick_at(start + secs(115)),
+            Some(start + secs(116))
+        );
+        assert_eq!(
+            engine.tracker().get_paused_time(start + secs(115)),
+            secs(100)
+        );
+    }
+
+    #[test]
+    fn test_break_finish_goes_idle() {
+        let start = Instant::now();
+        let mut engine = engine(1, 1);
+        engine.start_work(start);
+        engine.tick(start + secs(60));
+
+        let events = engine.tick(start + secs(120));
+        assert!(events.contains(&Event::PhaseEnded(PhaseSummary {
+            state: PomodoroState::ShortBreak,
+            order: 1,
+            planned: secs(60),
+            extended: secs(0),
+            snoozed: false,
+            overtime: None,
+            actual: secs(60),
+            paused: secs(0),
+            outcome: Outcome::Completed,
+        })));
+        assert!(!engine.is_running());
+
+        engine.start_work(start + secs(130));
+        assert_eq!(engine.order(), 2);
+    }
+
+    #[test]
+    fn test_reset_and_stop() {
+        let start = Instant::now();
+        let mut engine = engine(25, 5);
+        engine.start_work(start);
+        engine.tick(start + secs(90));
+
+        let events = engine.reset(start + secs(90));
+        match &events[0] {
+            Event::PhaseEnded(summary) => {
+                assert_eq!(summary.outcome, Outcome::Reset);
+                assert_eq!(summary.actual, secs(90));
+            }
+            other => panic!("expected the reset pomodoro to end, got {:?}", other),
+        }
+        assert_eq!(engine.order(), 1);
+        assert_eq!(engine.clock().get_time(), "25:00");
+
+        engine.pause(start + secs(100));
+        assert_eq!(
+            engine.stop(start + secs(130)),
+            vec![Event::PhaseEnded(PhaseSummary {
+                state: PomodoroState::Working,
+                order: 1,
+                planned: secs(25 * 60),
+                extended: secs(0),
+                snoozed: false,
+                overtime: None,
+                actual: secs(10),
+                paused: secs(30),
+                outcome: Outcome::Quit,
+            })]
+        );
+        assert!(!engine.is_running());
+        assert_eq!(engine.stop(start + secs(130)), vec![]);
+    }
+
+    #[test]
+    fn test_skip() {
+        let start = Instant::now();
+        let mut engine = engine(25, 5);
+        assert_eq!(engine.skip(start), vec![]);
+
+        engine.start_work(start);
+        engine.pause(start + secs(60));
+        let events = engine.skip(start + secs(90));
+        match &events[0] {
+            Event::PhaseEnded(summary) => {
+                assert_eq!(summary.outcome, Outcome::Skipped);
+                assert_eq!(summary.actual, secs(60));
+                assert_eq!(summary.paused, secs(30));
+            }
+            other => panic!("expected the work period to end, got {:?}", other),
+        }
+        assert_eq!(engine.state(), PomodoroState::ShortBreak);
+        assert!(!engine.is_paused());
+        assert_eq!(engine.clock().get_time(), "05:00");
+
+        engine.skip(start + secs(100));
+        assert!(!engine.is_running());
+        assert_eq!(engine.order(), 1);
+
+        // skipping waits on a break that has to be started by hand, same as running down
+        let mut engine = Engine::new(&PomodoroConfig {
+            auto_start_break: false,
+            ..PomodoroConfig::default()
+        });
+        engine.start_work(start);
+        engine.skip(start + secs(10));
+        assert!(!engine.is_running());
+        assert!(engine.is_break_next());
+    }
+
+    #[test]
+    fn test_extend_and_snooze() {
+        let start = Instant::now();
+        let mut engine = engine(1, 2);
+        assert_eq!(engine.extend(start, SNOOZE), vec![]);
+
+        engine.start_work(start);
+        engine.tick(start + secs(30));
+        assert_eq!(
+            engine.extend(start + secs(30), SNOOZE),
+            vec![Event::Extended {
+                state: PomodoroState::Working,
+                by: SNOOZE,
+            }]
+        );
+        assert_eq!(engine.clock().get_time(), "05:30");
+        assert_eq!(engine.tick(start + secs(359)).len(), 1);
+
+        let events = engine.tick(start + secs(360));
+        match &events[1] {
+            Event::PhaseEnded(summary) => {
+                assert_eq!(summary.planned, secs(60));
+                assert_eq!(summary.extended, SNOOZE);
+                assert_eq!(summary.actual, secs(360));
+            }
+            other => panic!("expected the work period to end, got {:?}", other),
+        }
+        assert_eq!(engine.state(), PomodoroState::ShortBreak);
+
+        // straight after work runs down, it's the work that gets more time, not the break
+        let events = engine.extend(start + secs(370), SNOOZE);
+        match &events[0] {
+            Event::PhaseEnded(summary) => assert_eq!(summary.outcome, Outcome::Skipped),
+            other => panic!("expected the break to be cut short, got {:?}", other),
+        }
+        assert_eq!(
+            events[1],
+            Event::PhaseStarted {
+                state: PomodoroState::Working,
+                order: 1,
+                duration: SNOOZE,
+            }
+        );
+
+        let events = engine.tick(start + secs(670));
+        match &events[1] {
+            Event::PhaseEnded(summary) => {
+                assert_eq!(summary.planned, SNOOZE);
+                assert_eq!(summary.extended, secs(0));
+                assert!(summary.snoozed);
+            }
+            other => panic!("expected the snooze to end, got {:?}", other),
+        }
+
+        // the snooze button means the work too, for as long as it can still be snoozed
+        engine.snooze(start + secs(680), SNOOZE);
+        assert_eq!(engine.state(), PomodoroState::Working);
+        assert_eq!(engine.order(), 1);
+        engine.tick(start + secs(980));
+        assert_eq!(engine.state(), PomodoroState::ShortBreak);
+
+        // later on in the break, it's the break
+        engine.extend(start + secs(1040), SNOOZE);
+        assert_eq!(engine.state(), PomodoroState::ShortBreak);
+        assert_eq!(engine.clock().get_time(), "06:00");
+
+        // and a snooze picked that late is ignored
+        assert_eq!(engine.snooze(start + secs(1050), SNOOZE), vec![]);
+        assert_eq!(engine.state(), PomodoroState::ShortBreak);
+
+        // nor does it mean the work once the break has run down too and nothing is running
+        engine.tick(start + secs(1400));
+        assert!(!engine.is_running());
+        assert_eq!(engine.extend(start + secs(1500), SNOOZE), vec![]);
+        assert_eq!(engine.snooze(start + secs(1500), SNOOZE), vec![]);
+        assert!(!engine.is_running());
+    }
+
+    #[test]
+    fn test_overtime() {
+        let start = Instant::now();
+        let mut engine = Engine::new(&PomodoroConfig {
+            work_time: 1,
+            overtime: true,
+            ..PomodoroConfig::default()
+        });
+        engine.start_work(start);
+
+        let events = engine.tick(start + secs(60));
+        assert_eq!(
+            events,
+            vec![
+                Event::Tick { remaining: secs(0) },
+                Event::Nudge {
+                    state: PomodoroState::Working,
+                    over: secs(0),
+                },
+            ]
+        );
+        assert!(engine.is_overtime());
+        assert_eq!(engine.state(), PomodoroState::Working);
+
+        assert_eq!(
+            engine.tick(start + secs(61)),
+            vec![Event::Overtime {
+                state: PomodoroState::Working,
+                over: secs(1),
+            }]
+        );
+        assert_eq!(engine.clock().get_time(), "00:01");
+
+        // a late tick jumping past two nudges only gives the one
+        let events = engine.tick(start + secs(60 + 150));
+        assert_eq!(
+            events[1],
+            Event::Nudge {
+                state: PomodoroState::Working,
+                over: secs(150),
+            }
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(engine.tick(start + secs(60 + 170)).len(), 1);
+        assert_eq!(engine.tick(start + secs(60 + 180)).len(), 2);
+
+        let events = engine.acknowledge(start + secs(60 + 192));
+        match &events[0] {
+            Event::PhaseEnded(summary) => {
+                assert_eq!(summary.outcome, Outcome::Completed);
+                assert_eq!(summary.actual, secs(60));
+                assert_eq!(summary.overtime, Some(secs(192)));
+            }
+            other => panic!("expected the work period to end, got {:?}", other),
+        }
+        assert!(!engine.is_overtime());
+        assert_eq!(engine.state(), PomodoroState::ShortBreak);
+    }
+
+    #[test]
+    fn test_sequence() {
+        let time = ManualClock::new();
+        let mut engine = Engine::new(&PomodoroConfig {
+            sequence: Some("50w 10b 30w 30w 30B".parse().unwrap()),
+            ..PomodoroConfig::default()
+        });
+        assert_eq!(engine.cycle_length(), 3);
+
+        let mut states = vec![];
+        for _ in 0..4 {
+            engine.start_work(time.now());
+            states.extend(run_until_idle(&mut engine, &time));
+        }
+
+        use PomodoroState::*;
+        assert_eq!(
+            states,
+            vec![
+                (Working, 1),
+                (ShortBreak, 1),
+                (Working, 2),
+                (Working, 3),
+                (LongBreak, 3),
+                (Working, 1),
+                (ShortBreak, 1),
+            ]
+        );
+        assert_eq!(
+            time.elapsed(),
+            secs((50 + 10 + 30 + 30 + 30 + 50 + 10) * 60)
+        );
+
+        // back to back work periods wait to be started, like any other pomodoro
+        engine.start_work(time.now());
+        time.sleep(secs(30 * 60));
+        engine.tick(time.now());
+        assert!(!engine.is_running());
+        assert!(!engine.is_break_next());
+        assert_eq!(engine.next_state(), Working);
+    }
+
+    #[test]
+    fn test_flowtime() {
+        let start = Instant::now();
+        let mut engine = Engine::new(&PomodoroConfig {
+            schedule: Schedule::Flowtime,
+            ..PomodoroConfig::default()
+        });
+        assert_eq!(
+            engine.start_work(start),
+            vec![Event::PhaseStarted {
+                state: PomodoroState::Working,
+                order: 1,
+                duration: secs(0),
+            }]
+        );
+        assert!(engine.is_flowing());
+
+        assert_eq!(
+            engine.tick(start + secs(3000)),
+            vec![Event::Elapsed {
+                elapsed: secs(3000)
+            }]
+        );
+        assert_eq!(engine.clock().get_time(), "50:00");
+        assert_eq!(engine.extend(start + secs(3000), SNOOZE), vec![]);
+
+        let events = engine.skip(start + secs(3000));
+        assert_eq!(
+            events,
+            vec![
+                Event::PhaseEnded(PhaseSummary {
+                    state: PomodoroState::Working,
+                    order: 1,
+                    planned: secs(3000),
+                    extended: secs(0),
+                    snoozed: false,
+                    overtime: None,
+                    actual: secs(3000),
+                    paused: secs(0),
+                    outcome: Outcome::Completed,
+                }),
+                Event::PhaseStarted {
+                    state: PomodoroState::ShortBreak,
+                    order: 1,
+                    duration: secs(600),
+                },
+            ]
+        );
+        assert!(!engine.is_flowing());
+
+        // the break counts down like any other
+        engine.tick(start + secs(3000 + 600));
+        assert!(!engine.is_running());
+    }
+
+    #[test]
+    fn test_warnings() {
+        let start = Instant::now();
+        let mut engine = Engine::new(&PomodoroConfig {
+            work_time: 10,
+            short_break_time: 1,
+            warnings: Warnings {
+                work: vec![2, 5, 30],
+                breaks: vec![1],
+            },
+            ..PomodoroConfig::default()
+        });
+        let warnings = |events: Vec<Event>| -> Vec<Event> {
+            events
+                .into_iter()
+                .filter(|event| matches!(event, Event::Warning { .. }))
+                .collect()
+        };
+
+        engine.start_work(start);
+        assert_eq!(warnings(engine.tick(start + secs(299))), vec![]);
+        assert_eq!(
+            warnings(engine.tick(start + secs(300))),
+            vec![Event::Warning {
+                state: PomodoroState::Working,
+                remaining: secs(5 * 60),
+            }]
+        );
+        assert_eq!(warnings(engine.tick(start + secs(301))), vec![]);
+
+        // a late tick still gets the warning it jumped past
+        assert_eq!(
+            warnings(engine.tick(start + secs(9 * 60))),
+            vec![Event::Warning {
+                state: PomodoroState::Working,
+                remaining: secs(2 * 60),
+            }]
+        );
+
+        // a warning as long as the break itself never goes off
+        engine.tick(start + secs(10 * 60));
+        assert_eq!(engine.state(), PomodoroState::ShortBreak);
+        assert_eq!(warnings(engine.tick(start + secs(10 * 60 + 1))), vec![]);
+    }
+
+    #[test]
+    fn test_auto_start() {
+        let start = Instant::now();
+        let mut engine = Engine::new(&PomodoroConfig {
+            work_time: 1,
+            short_break_time: 1,
+            auto_start_work: true,
+            auto_start_break: false,
+            grace: 10,
+            ..PomodoroConfig::default()
+        });
+
+        // the break waits to be started by hand
+        engine.start_work(start);
+        engine.tick(start + secs(60));
+        assert!(!engine.is_running());
+        assert!(engine.is_break_next());
+        assert_eq!(engine.starts_at(), None);
+        assert_eq!(engine.next_state(), PomodoroState::ShortBreak);
+
+        let events = engine.start_next(start + secs(70));
+        assert!(matches!(
+            events[0],
+            Event::PhaseStarted {
+                state: PomodoroState::ShortBreak,
+                ..
+            }
+        ));
+
+        // the next pomodoro counts down its grace period, then starts on its own
+        let events = engine.tick(start + secs(130));
+        assert_eq!(
+            events.last(),
+            Some(&Event::Grace {
+                next: PomodoroState::Working,
+                remaining: secs(10),
+            })
+        );
+        assert_eq!(engine.starts_at(), Some(start + secs(140)));
+        assert_eq!(
+            engine.next_tick_at(start + secs(130)),
+            Some(start + secs(131))
+        );
+        assert_eq!(engine.tick(start + Duration::from_millis(130_500)), vec![]);
+        assert_eq!(
+            engine.tick(start + secs(131)),
+            vec![Event::Grace {
+                next: PomodoroState::Working,
+                remaining: secs(9),
+            }]
+        );
+
+        let events = engine.tick(start + secs(145));
+        assert_eq!(
+            events,
+            vec![Event::PhaseStarted {
+                state: PomodoroState::Working,
+                order: 2,
+                duration: secs(60),
+            }]
+        );
+        // started when it was due to, not when we noticed
+        assert_eq!(engine.clock().get_time(), "01:00");
+        engine.tick(start + secs(146));
+        assert_eq!(engine.clock().get_time(), "00:54");
+
+        // holding leaves the break waiting to be started by hand
+        engine.tick(start + secs(200));
+        engine.start_next(start + secs(200));
+        engine.tick(start + secs(260));
+        assert!(engine.starts_at().is_some());
+        assert!(engine.hold());
+        assert!(!engine.hold());
+        assert_eq!(engine.tick(start + secs(400)), vec![]);
+        assert!(!engine.is_running());
+    }
+}
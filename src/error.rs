@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Something that went wrong while the timer was running.
+#[derive(Debug)]
+pub enum PomodoroError {
+    /// The terminal couldn't be set up, drawn to or read from.
+    Terminal(io::Error),
+    /// A finished phase couldn't be written to the history.
+    History(io::Error),
+    /// The control socket couldn't be listened on or read from.
+    Control(io::Error),
+    /// The daemon couldn't be set up - most likely because another one is already running.
+    Daemon(io::Error),
+    /// The desktop notification system couldn't be reached.  The terminal UI only ever shows this
+    /// as a warning - a missed notification is no reason to stop the clock.
+    Notification(notify_rust::Error),
+    /// One of the other notification backends couldn't write to the terminal, start its command
+    /// or write to its log.  Only ever shown as a warning, like `Notification`.
+    Notifier(io::Error),
+}
+
+impl fmt::Display for PomodoroError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PomodoroError::Terminal(error) => write!(f, "couldn't use the terminal: {}", error),
+            PomodoroError::History(error) => write!(f, "couldn't update the history: {}", error),
+            PomodoroError::Control(error) => {
+                write!(f, "couldn't use the control socket: {}", error)
+            }
+            PomodoroError::Daemon(error) => write!(f, "couldn't start the daemon: {}", error),
+            PomodoroError::Notification(error) => {
+                write!(f, "couldn't send a notification: {}", error)
+            }
+            PomodoroError::Notifier(error) => {
+                write!(f, "couldn't send a notification: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for PomodoroError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PomodoroError::Terminal(error)
+            | PomodoroError::History(error)
+            | PomodoroError::Control(error)
+            | PomodoroError::Daemon(error)
+            | PomodoroError::Notifier(error) => Some(error),
+            PomodoroError::Notification(error) => Some(error),
+        }
+    }
+}
+
+/// Reading and writing the terminal is most of what the session does, so plain IO errors are
+/// taken to come from there.
+impl From<io::Error> for PomodoroError {
+    fn from(error: io::Error) -> PomodoroError {
+        PomodoroError::Terminal(error)
+    }
+}
+
+impl From<notify_rust::Error> for PomodoroError {
+    fn from(error: notify_rust::Error) -> PomodoroError {
+        PomodoroError::Notification(error)
+    }
+}
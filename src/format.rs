@@ -0,0 +1,219 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::control::Status;
+use crate::state::PomodoroState;
+
+/// The placeholders a status format can use.
+const PLACEHOLDERS: &[&str] = &[
+    "phase",
+    "state",
+    "remaining",
+    "order",
+    "cycle_length",
+    "percentage",
+];
+
+/// A one line layout for the status of the running timer, e.g. `"{phase} {remaining}"`.
+/// Placeholders are written in braces:
+///
+/// * `{phase}` - "Working", "Short break", "Long break", "Paused", "Overtime" or "Waiting"
+/// * `{state}` - the raw state: `working`, `short_break`, `long_break` or `none`
+/// * `{remaining}` - what is left on the clock, e.g. "17:42" (or "+03:12" running over)
+/// * `{order}` and `{cycle_length}` - where we are in the cycle
+/// * `{percentage}` - how much of the phase has gone by, from 0 to 100
+///
+/// `{{` and `}}` print a brace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Format {
+    pieces: Vec<Piece>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    Placeholder(String),
+}
+
+impl Default for Format {
+    fn default() -> Format {
+        "{phase} {remaining}".parse().unwrap()
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Format, String> {
+        let mut pieces = vec![];
+        let mut text = String::new();
+        let mut chars = format.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("'{{{}' is missing its '}}'", name)),
+                        }
+                    }
+                    if !PLACEHOLDERS.contains(&name.as_str()) {
+                        return Err(format!(
+                            "'{{{}}}' isn't a placeholder (try one of: {})",
+                            name,
+                            PLACEHOLDERS.join(", ")
+                        ));
+                    }
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(text.split_off(0)));
+                    }
+                    pieces.push(Piece::Placeholder(name));
+                }
+                '}' => return Err("a '}' with no '{' before it - use '}}' for a brace".into()),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+
+        Ok(Format { pieces })
+    }
+}
+
+impl Format {
+    /// Fills the placeholders in with `status`.
+    pub fn render(&self, status: &Status) -> String {
+        let mut line = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => line.push_str(text),
+                Piece::Placeholder(name) => line.push_str(&match name.as_str() {
+                    "phase" => status.phase().to_string(),
+                    "state" => status.state.name().to_string(),
+                    "remaining" => status.remaining.clone(),
+                    "order" => status.order.to_string(),
+                    "cycle_length" => status.cycle_length.to_string(),
+                    "percentage" => status.percentage().to_string(),
+                    _ => unreachable!("placeholders are checked when the format is parsed"),
+                }),
+            }
+        }
+        line
+    }
+}
+
+/// One line of output for a waybar custom module with `"return-type": "json"`.  The class is
+/// the state (`working`, `short_break`, `long_break`), or `paused`, `overtime`, `idle` between
+/// pomodoros and `stopped` when no timer is running, so each can be styled on its own.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Waybar {
+    pub text: String,
+    pub tooltip: String,
+    pub class: &'static str,
+    pub percentage: u64,
+}
+
+impl Waybar {
+    /// The module for a running timer, with its text laid out by `format`.
+    pub fn new(status: &Status, format: &Format) -> Waybar {
+        let class = match status.state {
+            PomodoroState::None => "idle",
+            _ if status.paused => "paused",
+            _ if status.overtime => "overtime",
+            state => state.name(),
+        };
+
+        Waybar {
+            text: format.render(status),
+            tooltip: status.to_string(),
+            class,
+            percentage: status.percentage(),
+        }
+    }
+
+    /// The module when no timer is running.
+    pub fn stopped() -> Waybar {
+        Waybar {
+            text: String::new(),
+            tooltip: "No pomodoro running".to_string(),
+            class: "stopped",
+            percentage: 0,
+        }
+    }
+}
+
+impl fmt::Display for Waybar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(state: PomodoroState, paused: bool) -> Status {
+        Status {
+            state,
+            paused,
+            overtime: false,
+            remaining: "17:42".to_string(),
+            remaining_secs: 1062,
+            duration_secs: 1500,
+            order: 2,
+            cycle_length: 4,
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let working = status(PomodoroState::Working, false);
+        assert_eq!(Format::default().render(&working), "Working 17:42");
+
+        let format: Format = "{{{state}}} {order}/{cycle_length} {percentage}%"
+            .parse()
+            .unwrap();
+        assert_eq!(format.render(&working), "{working} 2/4 29%");
+
+        assert!("{phase} {left}".parse::<Format>().is_err());
+        assert!("{phase".parse::<Format>().is_err());
+        assert!("phase}".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_waybar() {
+        let paused = status(PomodoroState::Working, true);
+        let json = serde_json::to_value(Waybar::new(&paused, &Format::default())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "text": "Paused 17:42",
+                "tooltip": "Paused 17:42 (2 of 4)",
+                "class": "paused",
+                "percentage": 29,
+            })
+        );
+
+        let long_break = status(PomodoroState::LongBreak, false);
+        assert_eq!(
+            Waybar::new(&long_break, &Format::default()).class,
+            "long_break"
+        );
+        assert_eq!(Waybar::stopped().class, "stopped");
+    }
+}
@@ -0,0 +1,214 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Outcome, PhaseSummary};
+use crate::state::PomodoroState;
+use crate::xdg;
+
+/// Name of the history file inside the pomodoro data directory.
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// One line of the session history - a single work period or break, and how it went.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Wall clock time the phase started at.
+    pub started_at: DateTime<Local>,
+    pub phase: PomodoroState,
+    /// Position in the cycle (1 up to the cycle length).
+    pub order: u32,
+    /// How long the phase was meant to last, in seconds.
+    pub planned_secs: u64,
+    /// How much time was added on with `+`, in seconds.
+    #[serde(default)]
+    pub extended_secs: u64,
+    /// Whether the phase was brought back by a snooze (or `+` straight after it ran down), to
+    /// carry on the one before it.
+    #[serde(default)]
+    pub snoozed: bool,
+    /// How long the clock actually ran for, in seconds.  Pauses are not counted.
+    pub actual_secs: u64,
+    /// How long the phase spent paused, in seconds.
+    pub paused_secs: u64,
+    /// How long the phase ran over before it was acknowledged, in seconds.  Only phases run with
+    /// overtime on can have any.
+    #[serde(default)]
+    pub overtime_secs: u64,
+    pub outcome: Outcome,
+}
+
+impl HistoryEntry {
+    /// Builds an entry out of a phase that ended at `ended_at`.
+    pub fn from_summary(summary: &PhaseSummary, ended_at: DateTime<Local>) -> HistoryEntry {
+        let overtime = summary.overtime.unwrap_or_default();
+        let took = summary.actual + summary.paused + overtime;
+        let started_at = ended_at - chrono::Duration::from_std(took).unwrap_or_default();
+
+        HistoryEntry {
+            started_at,
+            phase: summary.state,
+            order: summary.order,
+            planned_secs: summary.planned.as_secs(),
+            extended_secs: summary.extended.as_secs(),
+            snoozed: summary.snoozed,
+            actual_secs: summary.actual.as_secs(),
+            paused_secs: summary.paused.as_secs(),
+            overtime_secs: overtime.as_secs(),
+            outcome: summary.outcome,
+        }
+    }
+}
+
+/// An append-only log of every phase that has ended, one JSON object per line.  Nothing is ever
+/// rewritten - entries only get added to the end of the file.
+#[derive(Clone, Debug)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// The history kept in the default place, `$XDG_DATA_HOME/pomodoro/history.jsonl` (falling
+    /// back to `~/.local/share/pomodoro/history.jsonl`).
+    pub fn open_default() -> io::Result<History> {
+        let dir = xdg::data_dir().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "couldn't work out where to keep history - set $XDG_DATA_HOME or $HOME",
+            )
+        })?;
+
+        Ok(History::at(dir.join(HISTORY_FILE)))
+    }
+
+    /// A history kept in the file at `path`.
+    pub fn at<P: Into<PathBuf>>(path: P) -> History {
+        History { path: path.into() }
+    }
+
+    /// Where the history file lives.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds an entry to the end of the history, creating the file (and its directory) the first
+    /// time around.
+    pub fn record(&self, entry: &HistoryEntry) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    /// Reads back every entry in the history, oldest first.  A history that hasn't been written
+    /// yet is simply empty.
+    pub fn entries(&self) -> io::Result<Vec<HistoryEntry>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = vec![];
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry = serde_json::from_str(&line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} line {}: {}", self.path.display(), i + 1, e),
+                )
+            })?;
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use std::time::Duration;
+
+    fn temp_history(name: &str) -> History {
+        let path = env::temp_dir()
+            .join(format!("pomodoro-test-{}", process::id()))
+            .join(name)
+            .join(HISTORY_FILE);
+        let _ = fs::remove_file(&path);
+        History::at(path)
+    }
+
+    fn summary(outcome: Outcome) -> PhaseSummary {
+        PhaseSummary {
+            state: PomodoroState::Working,
+            order: 2,
+            planned: Duration::from_secs(25 * 60),
+            extended: Duration::from_secs(0),
+            snoozed: false,
+            overtime: None,
+            actual: Duration::from_secs(10 * 60),
+            paused: Duration::from_secs(60),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_entry_from_summary() {
+        let ended_at = Local::now();
+        let entry = HistoryEntry::from_summary(&summary(Outcome::Reset), ended_at);
+
+        assert_eq!(ended_at - entry.started_at, chrono::Duration::minutes(11));
+        assert_eq!(entry.planned_secs, 1500);
+        assert_eq!(entry.actual_secs, 600);
+        assert_eq!(entry.paused_secs, 60);
+        assert_eq!(entry.order, 2);
+        assert_eq!(entry.outcome, Outcome::Reset);
+    }
+
+    #[test]
+    fn test_entry_before_extensions() {
+        // a line written before phases could be extended
+        let line = concat!(
+            r#"{"started_at":"2019-03-04T09:00:00+00:00","phase":"working","order":1,"#,
+            r#""planned_secs":1500,"actual_secs":1500,"paused_secs":0,"outcome":"completed"}"#,
+        );
+        let entry: HistoryEntry = serde_json::from_str(line).unwrap();
+        assert_eq!(entry.extended_secs, 0);
+        assert_eq!(entry.overtime_secs, 0);
+    }
+
+    #[test]
+    fn test_record_appends() {
+        let history = temp_history("appends");
+        assert_eq!(history.entries().unwrap(), vec![]);
+
+        let first = HistoryEntry::from_summary(&summary(Outcome::Completed), Local::now());
+        let second = HistoryEntry::from_summary(&summary(Outcome::Quit), Local::now());
+        history.record(&first).unwrap();
+        history.record(&second).unwrap();
+
+        assert_eq!(history.entries().unwrap(), vec![first, second]);
+
+        let raw = fs::read_to_string(history.path()).unwrap();
+        assert_eq!(raw.lines().count(), 2);
+        assert!(raw.contains(r#""phase":"working""#));
+        assert!(raw.contains(r#""outcome":"quit""#));
+    }
+}
@@ -0,0 +1,191 @@
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Event, Outcome};
+use crate::state::PomodoroState;
+
+/// How long a hook gets to run when no timeout is configured, in seconds.
+const DEFAULT_TIMEOUT: u64 = 10;
+
+/// How often a running hook is checked on to see if it has finished.
+const HOOK_POLL: Duration = Duration::from_millis(25);
+
+/// Shell commands to run as the timer moves between phases, set in the `[hooks]` table of the
+/// config file:
+///
+/// ```toml
+/// [hooks]
+/// on_work_start = "dnd on"
+/// on_work_end = "dnd off"
+/// timeout = 5
+/// ```
+///
+/// Each command is run with `sh -c`, with `POMODORO_PHASE` (`working`, `short_break` or
+/// `long_break`), `POMODORO_ORDER` and `POMODORO_DURATION` set.  `POMODORO_DURATION` is the
+/// planned length of the phase in seconds - except in a Flowtime work period, which has no planned
+/// length, so it is 0 as the period starts and the time worked as it ends.  Hooks run in the
+/// background - the clock never waits on them - and any still going after the timeout are killed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    /// Runs when a work period starts.
+    pub on_work_start: Option<String>,
+    /// Runs when a work period runs down or is skipped.
+    pub on_work_end: Option<String>,
+    /// Runs when a break starts.
+    pub on_break_start: Option<String>,
+    /// Runs when a break runs down or is skipped.
+    pub on_break_end: Option<String>,
+    /// Runs when a pomodoro is reset.
+    pub on_reset: Option<String>,
+    /// Runs when a phase is abandoned.
+    pub on_quit: Option<String>,
+    /// Seconds a hook gets before it is killed [default: 10].
+    pub timeout: Option<u64>,
+}
+
+impl Hooks {
+    /// Runs the hook for `event` in the background, if one is set.  Only starting the command
+    /// can fail - how it goes after that is its own business.
+    pub fn fire(&self, event: &Event) -> io::Result<()> {
+        if let Some((command, env)) = self.command_for(event) {
+            spawn(command, &env, self.timeout())?;
+        }
+        Ok(())
+    }
+
+    /// How long a hook gets before it is killed.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT))
+    }
+
+    /// The hook for `event` (if there is one) and the environment it runs with.
+    fn command_for(&self, event: &Event) -> Option<(&str, Vec<(&'static str, String)>)> {
+        let (hook, state, order, duration) = match event {
+            Event::PhaseStarted {
+                state,
+                order,
+                duration,
+            } => {
+                let hook = match state {
+                    PomodoroState::Working => &self.on_work_start,
+                    _ => &self.on_break_start,
+                };
+                (hook, *state, *order, *duration)
+            }
+            Event::PhaseEnded(summary) => {
+                let hook = match summary.outcome {
+                    Outcome::Reset => &self.on_reset,
+                    Outcome::Quit => &self.on_quit,
+                    Outcome::Completed | Outcome::Skipped => match summary.state {
+                        PomodoroState::Working => &self.on_work_end,
+                        _ => &self.on_break_end,
+                    },
+                };
+                (hook, summary.state, summary.order, summary.planned)
+            }
+            _ => return None,
+        };
+
+        let env = vec![
+            ("POMODORO_PHASE", state.name().to_string()),
+            ("POMODORO_ORDER", order.to_string()),
+            ("POMODORO_DURATION", duration.as_secs().to_string()),
+        ];
+        hook.as_deref().map(|command| (command, env))
+    }
+}
+
+/// Starts `command` and leaves a thread watching over it, which kills it (and anything it
+/// started) if it is still going after `timeout`.  The thread hands back how the command exited,
+/// or `None` if it had to be killed.
+pub(crate) fn spawn(
+    command: &str,
+    env: &[(&'static str, String)],
+    timeout: Duration,
+) -> io::Result<JoinHandle<Option<ExitStatus>>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+
+    let started = Instant::now();
+    Ok(thread::spawn(move || loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) if started.elapsed() < timeout => thread::sleep(HOOK_POLL),
+            _ => {
+                // the hook runs in its own process group, so this takes its children down too
+                unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::PhaseSummary;
+    use std::{env, fs, process};
+
+    #[test]
+    fn test_hook_environment() {
+        let out = env::temp_dir().join(format!("pomodoro-hook-{}", process::id()));
+        let hooks = Hooks {
+            on_break_end: Some(format!(
+                "echo $POMODORO_PHASE $POMODORO_ORDER $POMODORO_DURATION > {}",
+                out.display()
+            )),
+            ..Hooks::default()
+        };
+
+        let ended = Event::PhaseEnded(PhaseSummary {
+            state: PomodoroState::LongBreak,
+            order: 4,
+            planned: Duration::from_secs(20 * 60),
+            extended: Duration::from_secs(0),
+            snoozed: false,
+            overtime: None,
+            actual: Duration::from_secs(20 * 60),
+            paused: Duration::from_secs(0),
+            outcome: Outcome::Completed,
+        });
+        assert!(hooks.command_for(&Event::Paused).is_none());
+        assert!(hooks
+            .command_for(&Event::PhaseStarted {
+                state: PomodoroState::Working,
+                order: 1,
+                duration: Duration::from_secs(60),
+            })
+            .is_none());
+
+        let (command, env) = hooks.command_for(&ended).unwrap();
+        let status = spawn(command, &env, hooks.timeout())
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(status.unwrap().success());
+        assert_eq!(fs::read_to_string(&out).unwrap(), "long_break 4 1200\n");
+    }
+
+    #[test]
+    fn test_hook_timeout() {
+        let started = Instant::now();
+        let hook = spawn("sleep 5", &[], Duration::from_millis(100)).unwrap();
+
+        assert_eq!(hook.join().unwrap(), None);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -26,7 +26,7 @@
 //! minutes and a long break of 20 minutes.
 //!
 //! ```terminal
-//! $ pomodoro 
+//! $ pomodoro
 //! ```
 //!
 //! You can pass it terminal flags to customize the times.  `-w` flag will set the work time, `-s`
@@ -38,60 +38,304 @@
 //! $ pomodoro -w 30 -s 10 -l 25
 //! ```
 //!
+//! Periods can be as long as a day, so 90 minute deep-work blocks work fine. By default you get a
+//! long break after every 4 pomodoros; `-c` changes how many pomodoros make up a cycle:
+//!
+//! ```terminal
+//! $ pomodoro -w 90 -c 3
+//! ```
+//!
+//! If you always use the same times, put them in `~/.config/pomodoro/config.toml` (or
+//! `$XDG_CONFIG_HOME/pomodoro/config.toml`) instead. The file can also hold named profiles that you
+//! pick with `--profile`:
+//!
+//! ```toml
+//! work = 30
+//! short_break = 10
+//!
+//! [profiles.deep]
+//! work = 90
+//! long_break = 30
+//! cycle_length = 3
+//!
+//! [profiles.meeting-day]
+//! work = 15
+//! ```
+//!
+//! ```terminal
+//! $ pomodoro --profile deep
+//! ```
+//!
+//! Flags always win over the file, so `pomodoro --profile deep -w 60` gives you a 60 minute deep-work
+//! block. `--config` reads a different file.
+//!
+//! A `[hooks]` table in the same file runs shell commands as the timer moves between phases -
+//! `on_work_start`, `on_work_end`, `on_break_start`, `on_break_end`, `on_reset` and `on_quit`. Handy
+//! for turning on do-not-disturb or setting your Slack status while you work:
+//!
+//! ```toml
+//! [hooks]
+//! on_work_start = "dunstctl set-paused true; slack-status 'focusing until the bell'"
+//! on_work_end = "dunstctl set-paused false; slack-status ''"
+//! timeout = 5
+//! ```
+//!
+//! Hooks get `POMODORO_PHASE` (`working`, `short_break` or `long_break`), `POMODORO_ORDER` and
+//! `POMODORO_DURATION` (the length of the phase in seconds) in their environment. They run in the
+//! background so the clock never waits on them, and any still going after `timeout` seconds (10 by
+//! default) are killed.
+//!
+//! Notifications go to the desktop by default. A `[notify]` table picks where else (or instead) they
+//! go: `desktop`, `bell` (the terminal bell), `osc9` or `osc777` (escape sequences that kitty, iTerm2,
+//! WezTerm, foot and friends turn into notifications of their own), `command` (runs a shell command
+//! with `POMODORO_SUMMARY` and `POMODORO_BODY` set) and `log` (appends a line to a file):
+//!
+//! ```toml
+//! [notify]
+//! backends = ["osc777", "command", "log"]
+//! command = "ntfy publish pomodoro \"$POMODORO_SUMMARY\""
+//! log = "/home/me/.local/share/pomodoro/notifications.log"
+//! ```
+//!
+//! Plenty of notification daemons ignore the alarm sound, so pomodoro can play one itself. Build it
+//! with the `audio` feature (on Linux this needs the ALSA development package, e.g. `libasound2-dev`),
+//! add `sound` to the backends, and pick a WAV or OGG file - or the built in `chime` - for the end of
+//! work and the end of a break:
+//!
+//! ```terminal
+//! $ cargo +nightly install pomodoro --features audio
+//! ```
+//!
+//! ```toml
+//! [notify]
+//! backends = ["desktop", "sound"]
+//!
+//! [notify.sound]
+//! work_end = "/home/me/sounds/gong.ogg"
+//! break_end = "chime"
+//! volume = 60
+//! ```
+//!
+//! The same build can keep a focus cue going while you work: set `ambient` in `[notify.sound]` to
+//! `tick`, `white_noise` or `brown_noise` (with `ambient_volume`, 30 by default, to keep it in the
+//! background). It only plays while a work period is counting down - pausing or going on a break
+//! quiets it - and `t` switches it on and off, with the tick if nothing else is set.
+//!
+//! To get a heads up before a phase runs out, list how many minutes before the end to warn you in a
+//! `[warnings]` table. Warnings go out the same way as the end of phase notifications, and flash up
+//! in the clock box for a few seconds:
+//!
+//! ```toml
+//! [warnings]
+//! work = [5, 2]
+//! break = [1]
+//! ```
+//!
+//! Once work runs down the break starts by itself, and once a break runs down pomodoro waits for
+//! you to press `s`. `--auto-start-work` starts the next pomodoro without waiting too, and
+//! `--no-auto-start-break` waits for you before breaks as well. Give it a `--grace` period (in
+//! seconds) and you get a countdown first - "Next pomodoro starts in 10s" - where pressing any key
+//! holds the timer at the menu instead. The same settings go in the config file:
+//!
+//! ```toml
+//! auto_start_work = true
+//! auto_start_break = true
+//! grace = 10
+//! ```
+//!
+//! With `--overtime` (or `overtime = true` in the config file) a phase that runs down doesn't move
+//! on by itself. The clock turns red and counts up instead - "+03:12 over" - and you get nudged
+//! with another notification every minute until you press a key to take your break (or get back to
+//! work). `+` buys five more minutes instead, and `pomodoro skip` moves on when the timer runs as a
+//! daemon. How long each phase ran over is kept in the history, and `pomodoro stats` counts the
+//! overruns.
+//!
+//! The work, break and cycle lengths only get you the classic cycle. For anything else - three long
+//! sessions before a long break, say, or two work periods back to back - set a `sequence` in the
+//! config file (or in a profile, or pass `--sequence`). Each phase is its length in minutes followed
+//! by `w` for work, `b` for a short break or `B` for a long break, and the timer starts again from
+//! the top once it gets to the end:
+//!
+//! ```toml
+//! sequence = "50w 10b 50w 10b 50w 30B"
+//! ```
+//!
+//! Write it out as a list of tables instead and each phase can have a `name` (shown under the clock)
+//! and a `message` (shown in it) of its own:
+//!
+//! ```toml
+//! sequence = [
+//!     { kind = "work", duration = 90, name = "Deep work", message = "Heads down" },
+//!     { kind = "break", duration = 15 },
+//!     { kind = "work", duration = 30, name = "Email" },
+//!     { kind = "long_break", duration = 60, message = "Go for a walk" },
+//! ]
+//! ```
+//!
+//! If you'd rather work Flowtime style, `--schedule flowtime` (or `schedule = "flowtime"` in the
+//! config file, or in one of its profiles) does away with the set work period. The clock counts up
+//! from zero for as long as you keep going, and pressing `n` (or `pomodoro skip`) stops it and starts
+//! a break worked out from how long you worked - a fifth of it, unless you set a different `ratio`.
+//! Set `bands` to give work up to a certain length a break of its own:
+//!
+//! ```toml
+//! [flowtime]
+//! ratio = 5
+//! bands = [
+//!     { up_to = 25, break = 5 },
+//!     { up_to = 50, break = 8 },
+//!     { up_to = 90, break = 10 },
+//! ]
+//! ```
+//!
 //! All of the controls for starting, quitting or resetting a pomodoro are displayed by the
 //! pomodoro menu on launch. `s` will start your next pomodoro. `q` will take you back to the
 //! menu if you are in a pomodoro, or quit if you are at the menu. `r` will reset the current
-//! pomodoro (back to the head of the work cycle and immediately begin countdown).
+//! pomodoro (back to the head of the work cycle and immediately begin countdown). `p` will pause
+//! the running clock, and pressing `p` again picks up right where you left off. `n` skips ahead to
+//! the next phase, as if the clock had run down - though your history will show it as skipped. `+`
+//! gives you five more minutes; press it within a minute of work running out (or pick "Snooze 5m"
+//! on the notification) and you get five more minutes of work before your break. `t` switches the
+//! ticking on and off (see the sound settings above).
 //!
 //! Commands are listened for in an asynchronous and non-blocking fashion.
 //!
+//! Every work period and break you finish, reset or quit is added to a history file at
+//! `$XDG_DATA_HOME/pomodoro/history.jsonl` (or `~/.local/share/pomodoro/history.jsonl`), one JSON
+//! object per line, so you can look back at how many real pomodoros you got done.
+//!
+//! `pomodoro stats` sums that history up - pomodoros completed, focus time, interruptions and your
+//! longest streak - by day, week (`--by week`) or month (`--by month`). `--since` and `--until`
+//! narrow it down to a range of days, and `--json` prints it for scripts:
+//!
+//! ```terminal
+//! $ pomodoro stats --by week --since 2019-03-01 --json
+//! ```
+//!
+//! While it runs, the timer also listens on a Unix socket at `$XDG_RUNTIME_DIR/pomodoro.sock`, so
+//! editor plugins and window manager keybindings can drive it. Send it one JSON request per line -
+//! `start`, `pause`, `resume`, `reset`, `skip`, `extend`, `quit` or `status` - and it answers with a
+//! line saying whether that worked and where the timer is at:
+//!
+//! ```terminal
+//! $ echo '{"command":"pause"}' | socat - UNIX-CONNECT:$XDG_RUNTIME_DIR/pomodoro.sock | jq
+//! {
+//!   "ok": true,
+//!   "status": {
+//!     "state": "working",
+//!     "paused": true,
+//!     "remaining": "17:42",
+//!     "remaining_secs": 1062,
+//!     "duration_secs": 1500,
+//!     "order": 2,
+//!     "cycle_length": 4
+//!   }
+//! }
+//! ```
+//!
+//! The same requests are there as subcommands, which talk to the timer that is already running:
+//! `pomodoro status` (add `--json` for scripts), `pomodoro pause`, `pomodoro resume`,
+//! `pomodoro reset`, `pomodoro skip`, `pomodoro extend`, `pomodoro quit` and `pomodoro start`.
+//! `start` takes the same flags and `--profile` as the timer itself, to switch settings for the
+//! pomodoros from then on:
+//!
+//! ```terminal
+//! $ pomodoro start --profile deep
+//! Working 1:30:00 (1 of 3)
+//! ```
+//!
+//! They exit with status 2 when no timer is running, and 3 when the timer won't do what was asked
+//! (pausing when nothing is counting down, say).
+//!
+//! For status bars, `pomodoro status --format '{phase} {remaining}'` prints a single line, with
+//! `{phase}`, `{state}`, `{remaining}`, `{order}`, `{cycle_length}` and `{percentage}` to pick from.
+//! Add `--follow` to keep printing a fresh line every second instead of polling. `--waybar` prints
+//! the JSON a waybar custom module wants: `text`, `tooltip`, a `class` to style (`working`,
+//! `short_break`, `long_break`, `paused`, `overtime`, `idle` or `stopped`) and a `percentage`:
+//!
+//! ```json
+//! "custom/pomodoro": {
+//!     "exec": "pomodoro status --waybar --follow",
+//!     "return-type": "json"
+//! }
+//! ```
+//!
+//! To keep the timer running without a terminal at all, start it with `--daemon` (the usual flags and
+//! `--profile` still apply). It runs the same cycle, shows nothing but the desktop notifications,
+//! and waits for `pomodoro start` and friends. It keeps its pid in `$XDG_RUNTIME_DIR/pomodoro.pid`
+//! and won't start if another daemon is already running, which makes it easy to run as a systemd
+//! user service:
+//!
+//! ```ini
+//! [Service]
+//! ExecStart=%h/.cargo/bin/pomodoro --daemon
+//! ```
+//!
+//! # Embedding the timer
+//!
+//! The terminal UI is just one consumer of [`Engine`], a headless timer that owns the cycle and
+//! the clock. Hand it the current `Instant` and it hands back [`Event`]s for you to react to:
+//!
+//! ```rust
+//! use std::time::{Duration, Instant};
+//! use pomodoro::{Engine, Event, PomodoroConfig, PomodoroState};
+//!
+//! let config = PomodoroConfig {
+//!     work_time: 1,
+//!     ..PomodoroConfig::default()
+//! };
+//! let mut engine = Engine::new(&config);
+//!
+//! let start = Instant::now();
+//! engine.start_work(start);
+//!
+//! for event in engine.tick(start + Duration::from_secs(60)) {
+//!     if let Event::PhaseEnded(summary) = event {
+//!         assert_eq!(summary.state, PomodoroState::Working);
+//!     }
+//! }
+//! assert_eq!(engine.state(), PomodoroState::ShortBreak);
+//! ```
+//!
 //! Enjoy!
 
-#[macro_use]
-extern crate structopt;
-
-use std::io;
-use std::io::{Read, Write};
-use std::thread::sleep;
-use std::time::{Duration, Instant};
+pub mod client;
+mod clock;
+mod config;
+pub mod control;
+pub mod daemon;
+mod engine;
+mod error;
+pub mod format;
+mod history;
+mod hooks;
+pub mod notify;
+mod sequence;
+pub mod sound;
+mod state;
+pub mod stats;
+mod time;
+mod tui;
+mod xdg;
+
+pub use crate::client::ClientError;
+pub use crate::clock::Clock;
+pub use crate::config::{
+    Band, ConfigError, ConfigFile, Flowtime, PomodoroConfig, Schedule, Settings, Warnings,
+    MAX_CYCLE_LENGTH, MAX_MINUTES,
+};
+pub use crate::engine::{Engine, Event, Outcome, PhaseSummary};
+pub use crate::error::PomodoroError;
+pub use crate::history::{History, HistoryEntry};
+pub use crate::hooks::Hooks;
+pub use crate::notify::{Notifier, NotifySettings, Recorder};
+pub use crate::sequence::{Kind, Phase, Sequence};
+pub use crate::state::{PomodoroState, StateTracker, DEFAULT_CYCLE_LENGTH};
+pub use crate::time::{ManualClock, SystemClock, TimeSource};
+pub use crate::tui::{Command, PomodoroSession, CONTROLS, POMODORO_START_PROMPT};
 
 use std::error::Error;
-use termion::raw::IntoRawMode;
-use termion::{clear, cursor, style};
-
-use notify_rust::Notification;
-
-/// The pomodoro menu.
-const POMODORO_MENU: &'static str = "
-╔═════════════════╗
-║───┬ Pomodoro────║
-║ s ┆ start next  ║
-║ q ┆ quit        ║
-╚═══╧═════════════╝";
-
-/// Initial pomodoro welcome menu.
-pub const POMODORO_START_PROMPT: &'static str = "
-╔══════════════════════════════╗
-║──Start your first Pomodoro!─-║
-║──────────────────────────────║
-║ s ┆ start    Press s         ║
-║ q ┆ quit     to start!       ║
-║ r ┆ reset                    ║
-╚═══╧══════════════════════════╝";
-
-/// Controls layout always on screen when clock is rolling.
-pub const CONTROLS: &'static str = "
-------controls------
- q    ~ end current
- r    ~ reset
-";
-
-/// Pinging sound when clock is up.
-#[cfg(target_os = "macos")]
-static SOUND: &'static str = "Ping";
-
-#[cfg(all(unix, not(target_os = "macos")))]
-static SOUND: &'static str = "alarm-clock-elapsed";
+use std::path::PathBuf;
 
 /**
  * Terminal flag settings
@@ -100,542 +344,169 @@ use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "pomodoro", about = "a rust based pomodoro timer")]
-/// You can use this terminal program to start a pomodoro timer.
-pub struct PomodoroConfig {
-    #[structopt(short = "w", long = "work", default_value = "25")]
-    /// Sets length of work period in minutes.
-    work_time: u64,
-
-    #[structopt(short = "s", long = "shortbreak", default_value = "5")]
-    /// Sets length of your short break in minutes.
-    short_break_time: u64,
-
-    #[structopt(short = "l", long = "longbreak", default_value = "20")]
-    /// Sets length of your long break in minutes.
-    long_break_time: u64,
-}
-
-/// This struct represents a pomodoro session - which is from the start of running the application
-/// until you terminate it. Between that time this struct will keep track of the initial height and
-/// width of the terminal window when launching pomodoro, and keep a lock on stdin and stdout so we
-/// can draw to the screen, and also accept async input from the user. Lastly, this session also
-/// holds a state tracker, the clock itself (that gets drawn) and a config file.  The config is
-/// passed in by the user.  -w flag will pass in a custom work time, -s will pass in a custom short
-/// break time, and -l will pass in a custom long break time.
-pub struct PomodoroSession<R, W> {
-    stdin: R,
-    stdout: W,
-    width: u16,
-    height: u16,
-    pomodoro_tracker: StateTracker,
-    clock: Clock,
-    config: PomodoroConfig,
+/// Everything that can be passed on the command line.  With no subcommand the pomodoro timer
+/// starts up in the terminal.
+pub struct Cli {
+    #[structopt(long = "daemon")]
+    /// Runs the timer in the background without a terminal, controlled with the subcommands
+    /// below and showing nothing but desktop notifications.
+    pub daemon: bool,
+
+    #[structopt(flatten)]
+    pub options: PomodoroOptions,
+
+    #[structopt(subcommand)]
+    pub command: Option<Subcommand>,
 }
 
-impl<R: Read, W: Write> PomodoroSession<R, W> {
-    fn start(&mut self) {
-        write!(self.stdout, "{}", cursor::Hide).unwrap();
-        self.display_menu(Some(POMODORO_START_PROMPT));
-    }
-
-    fn begin_cycle(&mut self) {
-        self.start_work();
-        self.display_menu(None);
-    }
-
-    /// Call a start to a work cycle.
-    pub fn start_work(&mut self) {
-        self.pomodoro_tracker.set_work_state();
-        self.clock.set_time_minutes(self.config.work_time);
-        self.countdown();
-    }
-
-    /// Reset the current pomodoro by decrementing the cycle and re-running start_work.
-    pub fn reset_current_pomodoro(&mut self) {
-        self.pomodoro_tracker.decrement_cycle();
-        self.start_work();
-    }
-
-    /// Checks the pomodoro state (Working, ShortBreak, or LongBreak) and runs the appropraite
-    /// internal countdown method.
-    pub fn countdown(&mut self) {
-        match self.pomodoro_tracker.current_state {
-            PomodoroState::Working => self.countdown_work(),
-            PomodoroState::ShortBreak => {
-                self.countdown_break(self.config.short_break_time);
-            }
-            PomodoroState::LongBreak => {
-                self.countdown_break(self.config.long_break_time);
-            }
-            _ => (),
-        }
-    }
-
-    /// Countdown count for work - with syncing so we are never more than a ms off from true time.
-    pub fn countdown_work(&mut self) {
-        loop {
-            let true_elapsed: u64 = (self
-                .pomodoro_tracker
-                .started_at
-                .unwrap()
-                .elapsed()
-                .as_millis()) as u64;
-
-            // take work time in milliseconds and subtract from current clock time
-            // in milliseconds to get the current elapsed "clock time" - then
-            // correct any errors from actual elapsed time and add 1 second to
-            // sleep to sync our display clock
-            let clock_elapsed = (self.config.work_time * 60_000) - self.clock.get_ms_from_time();
-
-            let sync_offset = true_elapsed - clock_elapsed;
-
-            sleep(Duration::from_millis(1000 - sync_offset));
-
-            match self.async_command_listen() {
-                Command::Quit => return,
-                Command::Reset => return self.reset_current_pomodoro(),
-                _ => (),
-            }
-
-            if let Command::Quit = self.async_command_listen() {
-                return;
-            }
-
-            self.clock.decrement_one_second();
-            self.draw_work_screen();
-
-            if &self.clock.get_ms_from_time() == &0 {
-                break;
-            }
-        }
-        Notification::new()
-            .summary("Pomodoro Break!")
-            .body("It's Time For a Break!")
-            .appname("Pomodoro")
-            .sound_name(SOUND)
-            .icon("appointment-soon")
-            .show()
-            .unwrap();
-        self.pomodoro_tracker.set_break_state();
-        self.start_break();
-    }
-
-    /// Starts a break by matching which break state we are in (short or long) and then running the
-    /// appropriate break function.
-    pub fn start_break(&mut self) {
-        match self.pomodoro_tracker.current_state {
-            PomodoroState::ShortBreak => self.short_break(),
-            PomodoroState::LongBreak => self.long_break(),
-            _ => (),
-        }
-    }
-
-    /// Sets the break time by referencing the config (flags passed in on start) and then starts
-    /// the countdown clock.
-    pub fn short_break(&mut self) {
-        self.clock.set_time_minutes(self.config.short_break_time);
-        self.countdown();
-    }
-
-    /// Sets the break time by referencing the config (flags passed in on start) and then starts
-    /// the countdown clock.
-    pub fn long_break(&mut self) {
-        self.clock.set_time_minutes(self.config.long_break_time);
-        self.countdown();
-    }
-
-    /// Countdown clock for a break - extremely similar to countdown-work - separate because the
-    /// notifications after the loops are different.  Good place for a refactor.
-    pub fn countdown_break(&mut self, duration: u64) {
-        loop {
-            let true_elapsed: u64 = (self
-                .pomodoro_tracker
-                .started_at
-                .unwrap()
-                .elapsed()
-                .as_millis()) as u64;
-
-            // take break time in milliseconds and subtract from current clock time
-            // in milliseconds + work time in ms to get the current elapsed
-            // "clock time" - then correct any errors from actual elapsed time and
-            // add 1 second to sleep to sync our display clock
-            let clock_elapsed = (duration * 60_000) - self.clock.get_ms_from_time()
-                + (self.config.work_time * 60_000);
-
-            let sync_offset = true_elapsed - clock_elapsed;
-
-            sleep(Duration::from_millis(1000 - sync_offset));
-
-            match self.async_command_listen() {
-                Command::Quit => return,
-                Command::Reset => return self.reset_current_pomodoro(),
-                _ => (),
-            }
-
-            self.clock.decrement_one_second();
-            self.draw_break_screen();
-
-            if &self.clock.get_ms_from_time() == &0 {
-                break;
-            }
-        }
-        Notification::new()
-            .summary("Pomodoro Break Over")
-            .body("Ready for Another Round?")
-            .appname("Pomodoro")
-            .sound_name(SOUND)
-            .icon("appointment-soon")
-            .show()
-            .unwrap();
-    }
-
-    /**
-     * CLOCK AND DRAWING METHODS
-     */
-
-    /// Draws the work clock on the screen.
-    pub fn draw_work_screen(&mut self) {
-        let clock = self.clock.gen_clock("Time to Work!");
-        self.draw_work_count();
-        self.draw_controls_help();
-        self.draw_clock(clock);
-    }
-
-    /// Draws the break clock on the screen.
-    pub fn draw_break_screen(&mut self) {
-        let clock = self.clock.gen_clock("Time to Chill");
-        self.draw_work_count();
-        self.draw_clock(clock);
-        self.draw_controls_help();
-    }
-
-    /// Takes in an input string and prints it centered on the screen
-    /// Returns the last line it was printed on in case another method
-    /// needs to know that to clear space after it.
-    pub fn draw_centered(&mut self, item: &str, height_offset: Option<u16>) -> usize {
-        let lines = item.lines();
-        let line_vec = item.lines().collect::<Vec<_>>();
-
-        let h = line_vec.len() as u16;
-        let w = line_vec[1].chars().count();
-
-        let height_offset = if let Some(offset) = height_offset {
-            offset
-        } else {
-            0
-        };
-
-        let mut last_i = 0;
-        for (i, line) in lines.enumerate() {
-            write!(
-                self.stdout,
-                "{}{}{}",
-                cursor::Goto(
-                    (&self.width / 2) - (w / 2) as u16,
-                    (&self.height / 2) - (h / 2) + height_offset + i as u16
-                ),
-                clear::CurrentLine,
-                line,
-            )
-            .unwrap();
-
-            last_i = i;
-        }
-
-        last_i
-    }
-
-    /// Draws the clock on the screen.
-    pub fn draw_clock(&mut self, clock: String) {
-        self.draw_centered(&clock, None);
-    }
-
-    /// Draws the current work count on the screen.
-    ///
-    /// # Example:
-    ///  
-    /// "Work Period 1 or 4"
-    pub fn draw_work_count(&mut self) {
-        write!(
-            self.stdout,
-            "\r\n{}{}Work Period {} of 4",
-            cursor::Goto((&self.width / 2) - 9, (&self.height / 2) + 5),
-            clear::CurrentLine,
-            &self.pomodoro_tracker.current_order.unwrap(),
-        )
-        .unwrap();
-    }
-
-    /// Draws the section of the screen when the work and break clocks are rolling that actively
-    /// remind user of async commands they can issue at any time to restart or quit the current
-    /// cycle.
-    pub fn draw_controls_help(&mut self) {
-        self.draw_centered(CONTROLS, Some(8));
-    }
-
-    fn clear_lines(&mut self, lines: &[usize; 2]) {
-        for i in lines[0]..lines[1] {
-            write!(
-                self.stdout,
-                "{}{}",
-                cursor::Goto(1, (&self.height / 2) - 3 + i as u16),
-                clear::CurrentLine,
-            )
-            .unwrap();
-        }
-    }
-
-    /// Displays the pomodoro menu that is seen either at the start (large menu) or between
-    /// pomodoros (small menu)
-    pub fn display_menu(&mut self, menu: Option<&'static str>) {
-        let menu = if let Some(menu) = menu {
-            menu
-        } else {
-            POMODORO_MENU
-        };
-
-        let last_i = self.draw_centered(menu, None);
-
-        self.clear_lines(&[last_i + 1, last_i + 8]);
-
-        self.stdout.flush().unwrap();
-
-        match self.wait_for_next_command() {
-            Command::Start => self.begin_cycle(),
-            Command::Quit => return,
-            Command::Reset => (),
-            Command::None => (),
-        }
-    }
-
-    /// WAITS (in a loop) for the next user command (happens between pomodoros).
-    pub fn wait_for_next_command(&mut self) -> Command {
-        let mut command = Command::None;
-
-        while let Command::None = command {
-            let mut buf = [0];
-            self.stdin.read(&mut buf).unwrap();
-            command = match buf[0] {
-                b's' => Command::Start,
-                b'r' => Command::Reset,
-                b'q' => Command::Quit,
-                _ => continue,
-            }
-        }
-
-        command
-    }
-
-    /// listens for the next command while clock is counting down in a non-blocking (async)
-    /// fashion. 
-    pub fn async_command_listen(&mut self) -> Command {
-        let mut buf = [0];
-        self.stdin.read(&mut buf).unwrap();
-        let command = match buf[0] {
-            b'r' => Command::Reset,
-            b'q' => Command::Quit,
-            _ => Command::None,
-        };
-
-        command
-    }
-}
-
-/// A simple state tracker that keeps track of the pomodoro state, the current order we are in (1
-/// to 4 or None if we haven't begun our first pomodoro yet), and when the current pomodoro was
-/// started at.  (Started at is None between pomodoros).
-#[derive(Debug)]
-pub struct StateTracker {
-    current_order: Option<i32>,
-    current_state: PomodoroState,
-    started_at: Option<Instant>,
-}
-
-impl StateTracker {
-    pub fn new() -> StateTracker {
-        StateTracker {
-            current_order: None,
-            current_state: PomodoroState::None,
-            started_at: None,
-        }
-    }
-
-    fn increment_cycle(&mut self) {
-        let new_order = match self.current_order {
-            Some(num) if num < 4 => Some(num + 1),
-            _ => Some(1),
-        };
-        self.current_order = new_order;
-    }
-
-    fn decrement_cycle(&mut self) {
-        let new_order = match self.current_order {
-            Some(num) if num > 1 => Some(num - 1),
-            Some(num) if num == 1 => None,
-            _ => Some(1),
-        };
-        self.current_order = new_order;
-    }
-
-    /// Gets the current order we are at within a pomdoro cycle (4 pomodoros per cycle).
-    pub fn get_order(&self) -> Option<i32> {
-        self.current_order
-    }
-
-    /// Sets the current work state by storing the current time, setting our pomodoro state to
-    /// PomodoroState::Working, and then incrementing the cycle by one.
-    pub fn set_work_state(&mut self) {
-        let now = Instant::now();
-        self.started_at = Some(now);
-
-        self.current_state = PomodoroState::Working;
-        self.increment_cycle();
-    }
+#[derive(StructOpt, Debug)]
+/// The things pomodoro can do besides running the timer.
+pub enum Subcommand {
+    #[structopt(name = "stats")]
+    /// Summarises your pomodoro history by day, week or month.
+    Stats(stats::StatsOptions),
+
+    #[structopt(name = "status")]
+    /// Shows where the running timer is at.
+    Status(client::StatusOptions),
+
+    #[structopt(name = "start")]
+    /// Starts the next pomodoro on the running timer, with new settings if any are passed.
+    Start(PomodoroOptions),
+
+    #[structopt(name = "pause")]
+    /// Pauses the running timer.
+    Pause,
+
+    #[structopt(name = "resume")]
+    /// Picks a paused timer back up.
+    Resume,
+
+    #[structopt(name = "reset")]
+    /// Restarts the current pomodoro on the running timer.
+    Reset,
 
-    /// Sets the break state (ShortBreak, LongBreak, or None).
-    pub fn set_break_state(&mut self) {
-        let break_state = match self.current_order {
-            Some(_x @ 0..=3) => PomodoroState::ShortBreak,
-            Some(_x @ 4) => PomodoroState::LongBreak,
-            Some(_) => PomodoroState::None,
-            None => PomodoroState::None,
-        };
+    #[structopt(name = "skip")]
+    /// Skips ahead to the next phase on the running timer.
+    Skip,
 
-        self.current_state = break_state;
-    }
-}
+    #[structopt(name = "extend")]
+    /// Gives the running timer's phase five more minutes.
+    Extend,
 
-/// Simple struct to translate user keystrokes into command types we can enforce with matches.
-pub enum Command {
-    Start,
-    Reset,
+    #[structopt(name = "quit")]
+    /// Shuts the running timer down.
     Quit,
-    None,
-}
-
-#[derive(Debug)]
-enum PomodoroState {
-    Working,
-    ShortBreak,
-    LongBreak,
-    None,
 }
 
-/// A simple clock struct that displays minutes and seconds, and has methods for drawing a nice
-/// border around the current dispalyed time.
-pub struct Clock {
-    minutes: u64,
-    seconds: u64,
+#[derive(StructOpt, Clone, Debug, Default, PartialEq)]
+/// You can use this terminal program to start a pomodoro timer.  Anything not passed here comes
+/// from the config file (and the profile picked from it), or falls back to the defaults.
+pub struct PomodoroOptions {
+    #[structopt(short = "w", long = "work", parse(try_from_str = "parse_minutes"))]
+    /// Sets length of work period in minutes [default: 25].
+    pub work_time: Option<u64>,
+
+    #[structopt(
+        short = "s",
+        long = "shortbreak",
+        parse(try_from_str = "parse_minutes")
+    )]
+    /// Sets length of your short break in minutes [default: 5].
+    pub short_break_time: Option<u64>,
+
+    #[structopt(short = "l", long = "longbreak", parse(try_from_str = "parse_minutes"))]
+    /// Sets length of your long break in minutes [default: 20].
+    pub long_break_time: Option<u64>,
+
+    #[structopt(
+        short = "c",
+        long = "cycle-length",
+        parse(try_from_str = "parse_cycle_length")
+    )]
+    /// Sets how many pomodoros you work through before a long break [default: 4].
+    pub cycle_length: Option<u32>,
+
+    #[structopt(long = "sequence")]
+    /// Works through your own sequence of phases instead, e.g. "50w 10b 50w 10b 50w 30B" - the
+    /// minutes for each, followed by w (work), b (short break) or B (long break).
+    pub sequence: Option<Sequence>,
+
+    #[structopt(
+        long = "schedule",
+        raw(possible_values = r#"&["pomodoro", "flowtime"]"#)
+    )]
+    /// Works in fixed periods, or counts work up and earns breaks in proportion to it
+    /// [default: pomodoro].
+    pub schedule: Option<Schedule>,
+
+    #[structopt(long = "auto-start-work")]
+    /// Starts the next pomodoro on its own once a break is over.
+    pub auto_start_work: bool,
+
+    #[structopt(
+        long = "no-auto-start-work",
+        raw(conflicts_with = "\"auto_start_work\"")
+    )]
+    /// Waits for you to start each pomodoro (the default).
+    pub no_auto_start_work: bool,
+
+    #[structopt(long = "auto-start-break")]
+    /// Starts each break on its own once a work period is over (the default).
+    pub auto_start_break: bool,
+
+    #[structopt(
+        long = "no-auto-start-break",
+        raw(conflicts_with = "\"auto_start_break\"")
+    )]
+    /// Waits for you to start each break.
+    pub no_auto_start_break: bool,
+
+    #[structopt(long = "grace")]
+    /// Counts down this many seconds, giving you the chance to hold, before starting a pomodoro or
+    /// break on its own [default: 0].
+    pub grace: Option<u64>,
+
+    #[structopt(long = "overtime")]
+    /// Keeps counting up once a phase runs down, nudging you until you press a key to move on.
+    pub overtime: bool,
+
+    #[structopt(long = "no-overtime", raw(conflicts_with = "\"overtime\""))]
+    /// Moves on as soon as a phase runs down (the default).
+    pub no_overtime: bool,
+
+    #[structopt(short = "p", long = "profile")]
+    /// Uses the settings from a profile in your config file.
+    pub profile: Option<String>,
+
+    #[structopt(long = "config", parse(from_os_str))]
+    /// Reads settings from this file instead of ~/.config/pomodoro/config.toml.
+    pub config_file: Option<PathBuf>,
 }
 
-impl Clock {
-    /// Instantiates a new clock at 00:00.
-    pub fn new() -> Clock {
-        Clock {
-            minutes: 0,
-            seconds: 0,
-        }
-    }
-
-    /// Sets clock time in absolute milliseconds.
-    pub fn set_time_ms(&mut self, ms: u64) {
-        self.minutes = (ms / (1000 * 60)) % 60;
-        self.seconds = (ms / 1000) % 60;
-    }
-
-    /// Sets clock time in absolute minutes.
-    pub fn set_time_minutes(&mut self, minutes: u64) {
-        self.set_time_ms(minutes * 60000);
-    }
-
-    /// Decrements the clock by one full second.
-    pub fn decrement_one_second(&mut self) {
-        let mut time_in_ms = self.get_ms_from_time();
-        time_in_ms -= 1000;
-        self.set_time_ms(time_in_ms);
-    }
+/// Parses a period length in minutes, rejecting anything the clock can't count down from.
+pub fn parse_minutes(value: &str) -> Result<u64, String> {
+    let minutes = value
+        .parse::<u64>()
+        .map_err(|_| format!("'{}' is not a whole number of minutes", value))?;
 
-    /// Translate the current clock time back into milliseconds
-    pub fn get_ms_from_time(&mut self) -> u64 {
-        (self.minutes * 60000) + (self.seconds * 1000)
-    }
-
-    /// Translates the clock struct into a human readable string.
-    ///
-    /// # Example
-    ///
-    /// ```rust
-    /// let mut clock = pomodoro::Clock::new();
-    ///
-    /// clock.set_time_minutes(5);
-    ///
-    /// assert_eq!(clock.get_time(), "05:00".to_string());
-    /// ```
-    pub fn get_time(&self) -> String {
-        format!("{:02}:{:02}", self.minutes, self.seconds)
-    }
-
-    /// Given a message ("Get to Work", or "Time to Chill") this will generate a nicely displayed
-    /// clock with the message added.
-    pub fn gen_clock(&self, message: &str) -> String {
-        let clock = format!("
-╭───────────────────────────────────────╮
-│                                       │
-│             {}             │
-│                 {}                 │
-│                                       │
-╰───────────────────────────────────────╯
-", message, self.get_time());
-        clock
-    }
+    config::check_minutes(minutes)
 }
 
-// Initializes the pomodoro session (this is run from start to finish)
-fn init(width: u16, height: u16, config: PomodoroConfig) {
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock().into_raw_mode().unwrap();
-    let stdin = termion::async_stdin();
-
-    write!(stdout, "{}{}", clear::All, cursor::Goto(1, 1)).unwrap();
-    stdout.flush().unwrap();
-
-    let mut pomodoro_screen = PomodoroSession {
-        width: width,
-        height: height,
-        stdin: stdin,
-        stdout: stdout,
-        pomodoro_tracker: StateTracker::new(),
-        clock: Clock::new(),
-        config,
-    };
-
-    write!(
-        pomodoro_screen.stdout,
-        "{}{}",
-        clear::All,
-        cursor::Goto(1, 1)
-    )
-    .unwrap();
-
-    pomodoro_screen.start();
-
-    write!(
-        pomodoro_screen.stdout,
-        "{}{}{}{}",
-        clear::All,
-        style::Reset,
-        cursor::Goto(1, 1),
-        cursor::Show,
-    )
-    .unwrap();
-    pomodoro_screen.stdout.flush().unwrap();
+/// Parses the number of pomodoros in a cycle - a cycle needs at least one pomodoro in it.
+pub fn parse_cycle_length(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(length) => config::check_cycle_length(length),
+        Err(_) => Err(format!("'{}' is not a whole number of pomodoros", value)),
+    }
 }
 
 /// Basic run function that is called from the binary.  Takes the current terminal size, and config
 /// from terminal flags and passes that into our init function
 pub fn run(config: PomodoroConfig) -> Result<(), Box<dyn Error>> {
-    let (x, y) = termion::terminal_size().unwrap();
-    init(x, y, config);
+    let (x, y) = termion::terminal_size().map_err(PomodoroError::Terminal)?;
+    let history = History::open_default().map_err(PomodoroError::History)?;
+    tui::init(x, y, config, history)?;
 
     Ok(())
 }
@@ -645,42 +516,19 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_clock_ms() {
-        let mut clock = Clock::new();
-        clock.set_time_ms(60000);
-        assert_eq!(clock.get_time(), "01:00");
-    }
-
-    #[test]
-    fn test_clock_minutes() {
-        let mut clock = Clock::new();
-        clock.set_time_minutes(1);
-        assert_eq!(clock.get_time(), "01:00");
-    }
-
-    #[test]
-    fn test_start_cycle() {
-        let mut pstate = StateTracker::new();
-        pstate.increment_cycle();
-        assert_eq!(pstate.get_order().unwrap(), 1);
-    }
-
-    #[test]
-    fn test_increment_cycle() {
-        let mut pstate = StateTracker::new();
-        pstate.increment_cycle();
-        pstate.increment_cycle();
-        assert_eq!(pstate.get_order().unwrap(), 2);
+    fn test_parse_minutes() {
+        assert_eq!(parse_minutes("90"), Ok(90));
+        assert!(parse_minutes("0").is_err());
+        assert!(parse_minutes("-5").is_err());
+        assert!(parse_minutes("ten").is_err());
+        assert!(parse_minutes("1441").is_err());
     }
 
     #[test]
-    fn test_cycle_loop() {
-        let mut pstate = StateTracker::new();
-        pstate.increment_cycle();
-        pstate.increment_cycle();
-        pstate.increment_cycle();
-        pstate.increment_cycle();
-        pstate.increment_cycle();
-        assert_eq!(pstate.get_order(), Some(1));
+    fn test_parse_cycle_length() {
+        assert_eq!(parse_cycle_length("6"), Ok(6));
+        assert!(parse_cycle_length("0").is_err());
+        assert!(parse_cycle_length("many").is_err());
+        assert!(parse_cycle_length("4000000000").is_err());
     }
 }
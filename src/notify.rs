@@ -0,0 +1,552 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::thread;
+use std::time::Duration;
+
+use chrono::Local;
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+
+use crate::error::PomodoroError;
+use crate::hooks;
+use crate::sound::SoundSettings;
+use crate::state::PomodoroState;
+
+/// Pinging sound when clock is up.
+#[cfg(target_os = "macos")]
+static SOUND: &str = "Ping";
+
+#[cfg(all(unix, not(target_os = "macos")))]
+static SOUND: &str = "alarm-clock-elapsed";
+
+/// The action behind the "Snooze 5m" button on end of phase notifications.
+#[cfg(all(unix, not(target_os = "macos")))]
+const SNOOZE_ACTION: &str = "snooze";
+
+/// How long a notification command gets before it is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a notification says.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub summary: String,
+    pub body: String,
+    /// The phase that has just run out, if that's what the message is about - for backends (like
+    /// sound) that treat the end of work and the end of a break differently, and the desktop,
+    /// which offers to snooze it.
+    pub phase: Option<PomodoroState>,
+}
+
+impl Message {
+    pub fn new<S: Into<String>, B: Into<String>>(summary: S, body: B) -> Message {
+        Message {
+            summary: summary.into(),
+            body: body.into(),
+            phase: None,
+        }
+    }
+
+    /// The message sent when a phase in `state` runs out.
+    pub fn phase_end(state: PomodoroState) -> Message {
+        let message = match state {
+            PomodoroState::Working => Message::new("Pomodoro Break!", "It's Time For a Break!"),
+            _ => Message::new("Pomodoro Break Over", "Ready for Another Round?"),
+        };
+        Message {
+            phase: Some(state),
+            ..message
+        }
+    }
+
+    /// The nudge sent while a phase in `state` is running `over` - the usual end of phase message
+    /// at first, and then how far over it has gone.
+    pub fn nudge(state: PomodoroState, over: Duration) -> Message {
+        let over = match over.as_secs() / 60 {
+            0 => return Message::phase_end(state),
+            1 => "1 minute over".to_string(),
+            minutes => format!("{} minutes over", minutes),
+        };
+        let message = match state {
+            PomodoroState::Working => Message::new(over, "Your break is waiting."),
+            _ => Message::new(over, "Time to get back to work."),
+        };
+        Message {
+            phase: Some(state),
+            ..message
+        }
+    }
+
+    /// The message sent when a phase in `state` has only `remaining` left to go.
+    pub fn warning(state: PomodoroState, remaining: Duration) -> Message {
+        let left = match remaining.as_secs() / 60 {
+            1 => "1 minute left".to_string(),
+            minutes => format!("{} minutes left", minutes),
+        };
+        match state {
+            PomodoroState::Working => Message::new(left, "Time to start wrapping up."),
+            _ => Message::new(left, "Your break is nearly over."),
+        }
+    }
+}
+
+/// Somewhere to let you know a phase has run out.
+pub trait Notifier {
+    fn notify(&mut self, message: &Message) -> Result<(), PomodoroError>;
+
+    /// Whether snooze has been picked on a notification since the last time this was asked.
+    fn snoozed(&mut self) -> bool {
+        false
+    }
+}
+
+/// Pops up a desktop notification (with a sound) through the notification daemon.  The end of a
+/// phase comes with a "Snooze 5m" button, which only the latest such notification keeps - each
+/// one replaces the last.
+#[derive(Debug, Default)]
+pub struct Desktop {
+    snoozed: Arc<AtomicBool>,
+    /// The notification with the snooze button that is still up, and the thread waiting on it.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    waiting: Option<(u32, thread::JoinHandle<()>)>,
+}
+
+impl Desktop {
+    pub fn new() -> Desktop {
+        Desktop::default()
+    }
+}
+
+impl Notifier for Desktop {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn notify(&mut self, message: &Message) -> Result<(), PomodoroError> {
+        let mut notification = notification(message);
+        if message.phase.is_none() {
+            notification.show()?;
+            return Ok(());
+        }
+        notification.action(SNOOZE_ACTION, "Snooze 5m");
+
+        // the last one still up is replaced, and keeps the same id - so the thread waiting on
+        // its button goes on to wait on this one's
+        if let Some((id, waiter)) = &self.waiting {
+            if !waiter.is_finished() {
+                notification.id(*id).show()?;
+                return Ok(());
+            }
+        }
+
+        // waiting on the button blocks, and the handle can't leave the thread it was made on -
+        // so it is shown from a thread of its own, which says how that went before it waits
+        let snoozed = Arc::clone(&self.snoozed);
+        let (shown, result) = mpsc::sync_channel(1);
+        let waiter = thread::spawn(move || match notification.show() {
+            Ok(handle) => {
+                let _ = shown.send(Ok(handle.id()));
+                handle.wait_for_action(|action| {
+                    if action == SNOOZE_ACTION {
+                        snoozed.store(true, Ordering::Relaxed);
+                    }
+                });
+            }
+            Err(e) => {
+                let _ = shown.send(Err(e.to_string()));
+            }
+        });
+
+        match result.recv() {
+            Ok(Ok(id)) => {
+                self.waiting = Some((id, waiter));
+                Ok(())
+            }
+            Ok(Err(e)) => Err(PomodoroError::Notifier(io::Error::other(e))),
+            Err(_) => Err(PomodoroError::Notifier(io::Error::other(
+                "the notification thread died",
+            ))),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn notify(&mut self, message: &Message) -> Result<(), PomodoroError> {
+        notification(message).show()?;
+        Ok(())
+    }
+
+    fn snoozed(&mut self) -> bool {
+        self.snoozed.swap(false, Ordering::Relaxed)
+    }
+}
+
+fn notification(message: &Message) -> Notification {
+    let mut notification = Notification::new();
+    notification
+        .summary(&message.summary)
+        .body(&message.body)
+        .appname("Pomodoro")
+        .sound_name(SOUND)
+        .icon("appointment-soon");
+    notification
+}
+
+/// Gets the terminal's attention with an escape sequence written to stderr - a plain bell, or
+/// the OSC 9 (iTerm2, kitty, WezTerm) or OSC 777 (urxvt, foot, VTE) sequences that terminals
+/// turn into notifications of their own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Terminal {
+    Bell,
+    Osc9,
+    Osc777,
+}
+
+impl Terminal {
+    fn sequence(self, message: &Message) -> String {
+        // a stray escape or bell in the text would cut the sequence short
+        let clean = |text: &str| text.chars().filter(|c| !c.is_control()).collect::<String>();
+        match self {
+            Terminal::Bell => "\x07".to_string(),
+            Terminal::Osc9 => format!(
+                "\x1b]9;{}: {}\x07",
+                clean(&message.summary),
+                clean(&message.body)
+            ),
+            Terminal::Osc777 => format!(
+                "\x1b]777;notify;{};{}\x07",
+                clean(&message.summary).replace(';', ","),
+                clean(&message.body)
+            ),
+        }
+    }
+}
+
+impl Notifier for Terminal {
+    fn notify(&mut self, message: &Message) -> Result<(), PomodoroError> {
+        let mut stderr = io::stderr();
+        stderr
+            .write_all(self.sequence(message).as_bytes())
+            .and_then(|()| stderr.flush())
+            .map_err(PomodoroError::Notifier)
+    }
+}
+
+/// Runs a shell command in the background, with the message in `POMODORO_SUMMARY` and
+/// `POMODORO_BODY` - for phone push services, chat bots and the like.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunCommand {
+    command: String,
+}
+
+impl RunCommand {
+    pub fn new<S: Into<String>>(command: S) -> RunCommand {
+        RunCommand {
+            command: command.into(),
+        }
+    }
+}
+
+impl Notifier for RunCommand {
+    fn notify(&mut self, message: &Message) -> Result<(), PomodoroError> {
+        let env = [
+            ("POMODORO_SUMMARY", message.summary.clone()),
+            ("POMODORO_BODY", message.body.clone()),
+        ];
+        hooks::spawn(&self.command, &env, COMMAND_TIMEOUT).map_err(PomodoroError::Notifier)?;
+        Ok(())
+    }
+}
+
+/// Appends a timestamped line per notification to a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogFile {
+    path: PathBuf,
+}
+
+impl LogFile {
+    pub fn new<P: Into<PathBuf>>(path: P) -> LogFile {
+        LogFile { path: path.into() }
+    }
+
+    fn append(&self, message: &Message) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(
+            file,
+            "{} {}: {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            message.summary,
+            message.body
+        )
+    }
+}
+
+impl Notifier for LogFile {
+    fn notify(&mut self, message: &Message) -> Result<(), PomodoroError> {
+        self.append(message).map_err(PomodoroError::Notifier)
+    }
+}
+
+/// Keeps every message it is sent, for tests to look at.  Clones share the same list, so keep one
+/// and hand the other to the session.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    sent: Arc<Mutex<Vec<Message>>>,
+    snoozed: Arc<AtomicBool>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    /// Everything sent so far, oldest first.
+    pub fn messages(&self) -> Vec<Message> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Acts as though snooze had been picked on a notification.
+    pub fn snooze(&self) {
+        self.snoozed.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Notifier for Recorder {
+    fn notify(&mut self, message: &Message) -> Result<(), PomodoroError> {
+        self.sent.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+
+    fn snoozed(&mut self) -> bool {
+        self.snoozed.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// A notification backend, as it is named in the config file.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    Desktop,
+    Bell,
+    Osc9,
+    Osc777,
+    Command,
+    Log,
+    Sound,
+}
+
+/// Which backends notifications go out through, set in the `[notify]` table of the config file:
+///
+/// ```toml
+/// [notify]
+/// backends = ["desktop", "osc777", "log"]
+/// log = "/home/me/.local/share/pomodoro/notifications.log"
+/// ```
+///
+/// `command` needs a `command` to run and `log` a file to write to.  `sound` plays its sounds
+/// (set in `[notify.sound]`) itself, and is only there if pomodoro was built with the `audio`
+/// feature.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifySettings {
+    /// The backends to use [default: desktop].
+    pub backends: Vec<Backend>,
+    /// The shell command the `command` backend runs.
+    pub command: Option<String>,
+    /// The file the `log` backend appends to.
+    pub log: Option<PathBuf>,
+    /// What the `sound` backend plays.
+    pub sound: SoundSettings,
+}
+
+impl Default for NotifySettings {
+    fn default() -> NotifySettings {
+        NotifySettings {
+            backends: vec![Backend::Desktop],
+            command: None,
+            log: None,
+            sound: SoundSettings::default(),
+        }
+    }
+}
+
+impl NotifySettings {
+    /// Makes sure every backend picked has what it needs.
+    pub fn check(&self) -> Result<(), String> {
+        for backend in &self.backends {
+            match backend {
+                Backend::Command if self.command.is_none() => {
+                    return Err("the command backend needs a command to run".to_string())
+                }
+                Backend::Log if self.log.is_none() => {
+                    return Err("the log backend needs a log file to write to".to_string())
+                }
+                Backend::Sound if !cfg!(feature = "audio") => {
+                    return Err(
+                        "the sound backend needs pomodoro built with `--features audio`"
+                            .to_string(),
+                    )
+                }
+                _ => {}
+            }
+        }
+        if self.sound.ambient.is_some() && !cfg!(feature = "audio") {
+            return Err(
+                "the ambient sound needs pomodoro built with `--features audio`".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Sends each notification through every backend in turn.
+#[derive(Default)]
+pub struct Notifiers {
+    backends: Vec<Box<dyn Notifier>>,
+}
+
+impl Notifiers {
+    pub fn new() -> Notifiers {
+        Notifiers::default()
+    }
+
+    /// The backends picked in `settings`.  Any that are missing what they need (which
+    /// `NotifySettings::check` rules out for a config file) are left out.
+    pub fn from_settings(settings: &NotifySettings) -> Notifiers {
+        let mut notifiers = Notifiers::new();
+        for backend in &settings.backends {
+            match backend {
+                Backend::Desktop => notifiers.push(Desktop::new()),
+                Backend::Bell => notifiers.push(Terminal::Bell),
+                Backend::Osc9 => notifiers.push(Terminal::Osc9),
+                Backend::Osc777 => notifiers.push(Terminal::Osc777),
+                Backend::Command => {
+                    if let Some(command) = &settings.command {
+                        notifiers.push(RunCommand::new(command.as_str()));
+                    }
+                }
+                Backend::Log => {
+                    if let Some(path) = &settings.log {
+                        notifiers.push(LogFile::new(path.as_path()));
+                    }
+                }
+                #[cfg(feature = "audio")]
+                Backend::Sound => notifiers.push(crate::sound::Player::new(&settings.sound)),
+                #[cfg(not(feature = "audio"))]
+                Backend::Sound => {}
+            }
+        }
+        notifiers
+    }
+
+    /// Adds another backend.
+    pub fn push<N: Notifier + 'static>(&mut self, notifier: N) {
+        self.backends.push(Box::new(notifier));
+    }
+}
+
+/// One backend failing doesn't stop the others from being tried - the first error is handed back
+/// once they all have been.
+impl Notifier for Notifiers {
+    fn notify(&mut self, message: &Message) -> Result<(), PomodoroError> {
+        let mut result = Ok(());
+        for backend in &mut self.backends {
+            if let Err(e) = backend.notify(message) {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    fn snoozed(&mut self) -> bool {
+        // every backend is asked, so none is left holding a snooze for later
+        let mut snoozed = false;
+        for backend in &mut self.backends {
+            snoozed |= backend.snoozed();
+        }
+        snoozed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    struct Broken;
+
+    impl Notifier for Broken {
+        fn notify(&mut self, _: &Message) -> Result<(), PomodoroError> {
+            Err(PomodoroError::Notifier(io::Error::other("nobody home")))
+        }
+    }
+
+    #[test]
+    fn test_notifiers() {
+        let recorder = Recorder::new();
+        let log = env::temp_dir().join(format!("pomodoro-notify-{}.log", process::id()));
+        let _ = fs::remove_file(&log);
+
+        let mut notifiers = Notifiers::new();
+        notifiers.push(Broken);
+        notifiers.push(recorder.clone());
+        notifiers.push(LogFile::new(&log));
+
+        let message = Message::phase_end(PomodoroState::Working);
+        let error = notifiers.notify(&message).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "couldn't send a notification: nobody home"
+        );
+
+        // the backends after the broken one still got the message
+        assert_eq!(recorder.messages(), vec![message]);
+
+        recorder.snooze();
+        assert!(notifiers.snoozed());
+        assert!(!notifiers.snoozed());
+        let logged = fs::read_to_string(&log).unwrap();
+        assert!(logged.ends_with(" Pomodoro Break!: It's Time For a Break!\n"));
+    }
+
+    #[test]
+    fn test_terminal_sequences() {
+        let message = Message::new("Break; now", "Stretch\x1b[2J");
+        assert_eq!(Terminal::Bell.sequence(&message), "\x07");
+        assert_eq!(
+            Terminal::Osc9.sequence(&message),
+            "\x1b]9;Break; now: Stretch[2J\x07"
+        );
+        assert_eq!(
+            Terminal::Osc777.sequence(&message),
+            "\x1b]777;notify;Break, now;Stretch[2J\x07"
+        );
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings: NotifySettings = toml::from_str("backends = [\"bell\", \"log\"]").unwrap();
+        assert_eq!(settings.backends, vec![Backend::Bell, Backend::Log]);
+        assert!(settings.check().is_err());
+        assert!(toml::from_str::<NotifySettings>("backends = [\"pigeon\"]").is_err());
+
+        let settings: NotifySettings =
+            toml::from_str("backends = [\"sound\"]\n[sound]\nbreak_end = \"/tmp/gong.wav\"\n")
+                .unwrap();
+        assert_eq!(settings.check().is_ok(), cfg!(feature = "audio"));
+
+        let settings = NotifySettings::default();
+        assert!(settings.check().is_ok());
+        assert_eq!(Notifiers::from_settings(&settings).backends.len(), 1);
+    }
+}
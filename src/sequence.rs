@@ -0,0 +1,273 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+
+use crate::config::check_minutes;
+use crate::state::PomodoroState;
+
+/// Longest name or message that still fits inside the clock box.
+const MAX_MESSAGE: usize = 39;
+
+/// What sort of phase a step in a sequence is.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// A work period - `w` in a sequence string.
+    Work,
+    /// A short break - `b`.
+    Break,
+    /// A long break - `B`.
+    LongBreak,
+}
+
+impl Kind {
+    /// The state the timer is in during a phase of this kind.
+    pub fn state(self) -> PomodoroState {
+        match self {
+            Kind::Work => PomodoroState::Working,
+            Kind::Break => PomodoroState::ShortBreak,
+            Kind::LongBreak => PomodoroState::LongBreak,
+        }
+    }
+}
+
+/// One step of a sequence.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Phase {
+    pub kind: Kind,
+    /// Length of the phase in minutes.
+    pub duration: u64,
+    /// What the phase is called, shown under the clock in place of "Work Period".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// What the clock says during the phase, in place of "Time to Work!" or "Time to Chill".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Phase {
+    /// A phase of `kind` lasting `duration` minutes, with nothing special to say.
+    pub fn new(kind: Kind, duration: u64) -> Phase {
+        Phase {
+            kind,
+            duration,
+            name: None,
+            message: None,
+        }
+    }
+}
+
+/// The phases the timer works through, starting again from the top once it gets to the end.  Set
+/// as `sequence` in the config file, either as a string of minutes followed by `w` (work), `b`
+/// (short break) or `B` (long break):
+///
+/// ```toml
+/// sequence = "50w 10b 50w 10b 50w 30B"
+/// ```
+///
+/// or as a list of tables, which can give each phase a name and a message for the clock too:
+///
+/// ```toml
+/// sequence = [
+///     { kind = "work", duration = 90, name = "Deep work", message = "Heads down" },
+///     { kind = "break", duration = 15 },
+///     { kind = "work", duration = 30, name = "Email" },
+///     { kind = "long_break", duration = 60, message = "Go for a walk" },
+/// ]
+/// ```
+///
+/// A sequence always starts with a work period.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Sequence(Vec<Phase>);
+
+impl Sequence {
+    /// Checks that `phases` make a sequence the timer can work through.
+    pub fn new(phases: Vec<Phase>) -> Result<Sequence, String> {
+        match phases.first() {
+            None => return Err("a sequence needs at least one phase".to_string()),
+            Some(phase) if phase.kind != Kind::Work => {
+                return Err("a sequence has to start with a work period".to_string())
+            }
+            _ => (),
+        }
+        for phase in &phases {
+            check_minutes(phase.duration)?;
+            if let Some(name) = &phase.name {
+                if name.chars().count() > MAX_MESSAGE {
+                    return Err(format!(
+                        "'{}' won't fit in the clock (keep names to {} characters)",
+                        name, MAX_MESSAGE
+                    ));
+                }
+            }
+            if let Some(message) = &phase.message {
+                if message.chars().count() > MAX_MESSAGE {
+                    return Err(format!(
+                        "'{}' won't fit in the clock (keep messages to {} characters)",
+                        message, MAX_MESSAGE
+                    ));
+                }
+            }
+        }
+        Ok(Sequence(phases))
+    }
+
+    /// The classic cycle - `cycle_length` work periods with a short break after each, except the
+    /// last, which gets a long break.
+    pub fn classic(work: u64, short_break: u64, long_break: u64, cycle_length: u32) -> Sequence {
+        let mut phases = vec![];
+        for order in 1..=cycle_length {
+            phases.push(Phase::new(Kind::Work, work));
+            match order == cycle_length {
+                true => phases.push(Phase::new(Kind::LongBreak, long_break)),
+                false => phases.push(Phase::new(Kind::Break, short_break)),
+            }
+        }
+        Sequence(phases)
+    }
+
+    /// The phases in order.
+    pub fn phases(&self) -> &[Phase] {
+        &self.0
+    }
+}
+
+impl FromStr for Sequence {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Sequence, String> {
+        let phases = value
+            .split_whitespace()
+            .map(|step| {
+                let kind = match step.chars().last() {
+                    Some('w') => Kind::Work,
+                    Some('b') => Kind::Break,
+                    Some('B') => Kind::LongBreak,
+                    _ => return Err(bad_step(step)),
+                };
+                let minutes = step[..step.len() - 1].parse().map_err(|_| bad_step(step))?;
+                Ok(Phase::new(kind, minutes))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Sequence::new(phases)
+    }
+}
+
+fn bad_step(step: &str) -> String {
+    format!(
+        "'{}' isn't a phase - write the minutes followed by w (work), b (break) or B (long break), \
+         e.g. 50w",
+        step
+    )
+}
+
+impl<'de> Deserialize<'de> for Sequence {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Sequence, D::Error> {
+        struct SequenceVisitor;
+
+        impl<'de> Visitor<'de> for SequenceVisitor {
+            type Value = Sequence;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a sequence like \"50w 10b 50w 30B\", or a list of phases")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Sequence, E> {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Sequence, A::Error> {
+                let phases = Vec::deserialize(de::value::SeqAccessDeserializer::new(seq))?;
+                Sequence::new(phases).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_any(SequenceVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct File {
+        sequence: Sequence,
+    }
+
+    #[test]
+    fn test_parse_string() {
+        let sequence: Sequence = "50w 10b  50w 30B".parse().unwrap();
+        assert_eq!(
+            sequence.phases(),
+            &[
+                Phase::new(Kind::Work, 50),
+                Phase::new(Kind::Break, 10),
+                Phase::new(Kind::Work, 50),
+                Phase::new(Kind::LongBreak, 30),
+            ]
+        );
+        assert_eq!(
+            "25w 5b 25w 20B".parse::<Sequence>().unwrap(),
+            Sequence::classic(25, 5, 20, 2)
+        );
+
+        let error = "50w 10x".parse::<Sequence>().unwrap_err();
+        assert!(error.starts_with("'10x' isn't a phase"), "{}", error);
+        assert!("50w b".parse::<Sequence>().is_err());
+        assert!("50w 0b".parse::<Sequence>().is_err());
+        assert!("".parse::<Sequence>().is_err());
+        assert_eq!(
+            "10b 50w".parse::<Sequence>().unwrap_err(),
+            "a sequence has to start with a work period"
+        );
+    }
+
+    #[test]
+    fn test_parse_tables() {
+        let file: File = toml::from_str(
+            r#"
+sequence = [
+    { kind = "work", duration = 90, name = "Deep work", message = "Heads down" },
+    { kind = "long_break", duration = 30 },
+]
+"#,
+        )
+        .unwrap();
+        let phases = file.sequence.phases();
+        assert_eq!(phases[0].name.as_deref(), Some("Deep work"));
+        assert_eq!(phases[0].message.as_deref(), Some("Heads down"));
+        assert_eq!(phases[1], Phase::new(Kind::LongBreak, 30));
+
+        // round trips through JSON, as it does over the control socket
+        let json = serde_json::to_string(&file.sequence).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Sequence>(&json).unwrap(),
+            file.sequence
+        );
+
+        let error =
+            toml::from_str::<File>("sequence = [{ kind = \"nap\", duration = 20 }]\n").unwrap_err();
+        assert!(error.to_string().contains("nap"), "{}", error);
+        let long = "x".repeat(MAX_MESSAGE + 1);
+        for field in ["name", "message"] {
+            let toml = format!(
+                "sequence = [{{ kind = \"work\", duration = 25, {} = \"{}\" }}]\n",
+                field, long
+            );
+            let error = toml::from_str::<File>(&toml).unwrap_err();
+            assert!(error.to_string().contains("won't fit"), "{}", error);
+        }
+        let error = toml::from_str::<File>("sequence = \"50w 10y\"\n").unwrap_err();
+        assert!(
+            error.to_string().contains("'10y' isn't a phase"),
+            "{}",
+            error
+        );
+    }
+}
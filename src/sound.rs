@@ -0,0 +1,515 @@
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::engine::Event;
+use crate::state::PomodoroState;
+
+#[cfg(feature = "audio")]
+pub use self::player::Player;
+
+/// The name that picks the built in chime instead of a sound file.
+const CHIME: &str = "chime";
+
+/// A sound that loops in the background while you work.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AmbientSound {
+    /// A clockwork tick, once a second.
+    Tick,
+    WhiteNoise,
+    BrownNoise,
+}
+
+/// A sound to play - the built in chime, or a WAV or OGG file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Sound {
+    Chime,
+    File(PathBuf),
+}
+
+impl From<String> for Sound {
+    fn from(name: String) -> Sound {
+        match name.as_str() {
+            CHIME => Sound::Chime,
+            _ => Sound::File(PathBuf::from(name)),
+        }
+    }
+}
+
+impl From<Sound> for String {
+    fn from(sound: Sound) -> String {
+        match sound {
+            Sound::Chime => CHIME.to_string(),
+            Sound::File(path) => path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// The sounds the `sound` notification backend plays, set in the `[notify.sound]` table of the
+/// config file:
+///
+/// ```toml
+/// [notify.sound]
+/// work_end = "/home/me/sounds/gong.ogg"
+/// break_end = "chime"
+/// volume = 60
+/// ambient = "brown_noise"
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoundSettings {
+    /// Played when a work period runs out [default: chime].
+    pub work_end: Sound,
+    /// Played when a break runs out [default: chime].
+    pub break_end: Sound,
+    /// How loud, from 0 to 100 [default: 80].
+    #[serde(deserialize_with = "volume")]
+    pub volume: u8,
+    /// Loops while you work, from the moment the timer starts.  Toggled with `t` either way.
+    pub ambient: Option<AmbientSound>,
+    /// How loud the ambient loop is, from 0 to 100 [default: 30].
+    #[serde(deserialize_with = "volume")]
+    pub ambient_volume: u8,
+}
+
+impl Default for SoundSettings {
+    fn default() -> SoundSettings {
+        SoundSettings {
+            work_end: Sound::Chime,
+            break_end: Sound::Chime,
+            volume: 80,
+            ambient: None,
+            ambient_volume: 30,
+        }
+    }
+}
+
+fn volume<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    match u8::deserialize(deserializer)? {
+        volume if volume > 100 => Err(serde::de::Error::custom(format!(
+            "a volume of {} is too loud (it goes up to 100)",
+            volume
+        ))),
+        volume => Ok(volume),
+    }
+}
+
+/// The ambient loop - ticking or noise that plays only while a work period is counting down,
+/// and falls quiet when the clock is paused or on a break.
+pub struct Ambience {
+    #[cfg(feature = "audio")]
+    sound: AmbientSound,
+    #[cfg(feature = "audio")]
+    volume: u8,
+    enabled: bool,
+    state: PomodoroState,
+    paused: bool,
+    #[cfg(feature = "audio")]
+    player: Option<player::Loop>,
+}
+
+impl Ambience {
+    /// The loop set in `settings` - switched on already if `ambient` is set, off (with the tick
+    /// ready to go) otherwise.
+    pub fn new(settings: &SoundSettings) -> Ambience {
+        Ambience {
+            #[cfg(feature = "audio")]
+            sound: settings.ambient.unwrap_or(AmbientSound::Tick),
+            #[cfg(feature = "audio")]
+            volume: settings.ambient_volume,
+            enabled: settings.ambient.is_some(),
+            state: PomodoroState::None,
+            paused: false,
+            #[cfg(feature = "audio")]
+            player: None,
+        }
+    }
+
+    /// Whether the loop is switched on (even if it is quiet for now).
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether the loop is switched on and a work period is counting down.
+    pub fn is_playing(&self) -> bool {
+        self.enabled && self.state == PomodoroState::Working && !self.paused
+    }
+
+    /// Switches the loop on or off.
+    pub fn toggle(&mut self) -> io::Result<()> {
+        self.enabled = !self.enabled;
+        self.update()
+    }
+
+    /// Keeps up with the timer, starting and stopping the loop as work periods come and go.
+    pub fn follow(&mut self, event: &Event) -> io::Result<()> {
+        match event {
+            Event::PhaseStarted { state, .. } => {
+                self.state = *state;
+                self.paused = false;
+            }
+            Event::PhaseEnded(_) => self.state = PomodoroState::None,
+            Event::Paused => self.paused = true,
+            Event::Resumed => self.paused = false,
+            Event::Tick { .. }
+            | Event::Elapsed { .. }
+            | Event::Grace { .. }
+            | Event::Warning { .. }
+            | Event::Extended { .. }
+            | Event::Overtime { .. }
+            | Event::Nudge { .. } => return Ok(()),
+        }
+        self.update()
+    }
+
+    #[cfg(feature = "audio")]
+    fn update(&mut self) -> io::Result<()> {
+        let playing = self.is_playing();
+        if playing && self.player.is_none() {
+            self.player = Some(player::Loop::start(self.sound, self.volume)?);
+        }
+        if let Some(player) = &self.player {
+            player.set_playing(playing);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "audio"))]
+    fn update(&mut self) -> io::Result<()> {
+        if self.enabled {
+            self.enabled = false;
+            return Err(io::Error::other(
+                "no sound - pomodoro was built without the audio feature",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "audio")]
+mod player {
+    use std::f32::consts::PI;
+    use std::fs::File;
+    use std::io::{self, BufReader};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use rodio::buffer::SamplesBuffer;
+    use rodio::{Decoder, OutputStream, Sink, Source};
+
+    use super::{AmbientSound, Sound, SoundSettings};
+    use crate::error::PomodoroError;
+    use crate::notify::{Message, Notifier};
+    use crate::state::PomodoroState;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    /// The notes of the chime - when each one is struck (in seconds) and its pitch.
+    const CHIME_NOTES: [(f32, f32); 2] = [(0.0, 880.0), (0.18, 1318.5)];
+
+    /// How long the chime rings for, in seconds.
+    const CHIME_LENGTH: f32 = 1.5;
+
+    type Samples = Box<dyn Source<Item = f32> + Send>;
+
+    /// Plays the sound for the phase that has run out through the speakers.  The sound plays in
+    /// the background - only finding the file and the audio device hold anything up.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Player {
+        settings: SoundSettings,
+    }
+
+    impl Player {
+        pub fn new(settings: &SoundSettings) -> Player {
+            Player {
+                settings: settings.clone(),
+            }
+        }
+
+        fn play(&self, sound: &Sound) -> io::Result<()> {
+            let samples: Samples = match sound {
+                Sound::Chime => Box::new(SamplesBuffer::new(1, SAMPLE_RATE, chime())),
+                Sound::File(path) => {
+                    let file = BufReader::new(File::open(path)?);
+                    Box::new(
+                        Decoder::new(file)
+                            .map_err(io::Error::other)?
+                            .convert_samples(),
+                    )
+                }
+            };
+            let volume = f32::from(self.settings.volume) / 100.0;
+
+            // the output stream can't leave the thread it was opened on, so the whole thing
+            // happens over there and only word of whether it got going comes back
+            let (started, started_rx) = mpsc::sync_channel(1);
+            thread::spawn(move || {
+                let sink = OutputStream::try_default()
+                    .map_err(|e| e.to_string())
+                    .and_then(|(stream, handle)| {
+                        let sink = Sink::try_new(&handle).map_err(|e| e.to_string())?;
+                        Ok((stream, sink))
+                    });
+
+                match sink {
+                    Ok((_stream, sink)) => {
+                        sink.set_volume(volume);
+                        sink.append(samples);
+                        let _ = started.send(Ok(()));
+                        sink.sleep_until_end();
+                    }
+                    Err(e) => {
+                        let _ = started.send(Err(e));
+                    }
+                }
+            });
+
+            match started_rx.recv() {
+                Ok(result) => result.map_err(io::Error::other),
+                Err(_) => Err(io::Error::other("the sound player gave up")),
+            }
+        }
+    }
+
+    impl Notifier for Player {
+        fn notify(&mut self, message: &Message) -> Result<(), PomodoroError> {
+            let sound = match message.phase {
+                Some(PomodoroState::Working) => &self.settings.work_end,
+                Some(_) => &self.settings.break_end,
+                None => return Ok(()),
+            };
+            self.play(sound).map_err(PomodoroError::Notifier)
+        }
+    }
+
+    /// The ambient loop, playing on an output stream of its own.  The stream lives on a thread
+    /// that hangs around until the loop is dropped.
+    pub struct Loop {
+        sink: Sink,
+        _stop: mpsc::Sender<()>,
+    }
+
+    impl Loop {
+        /// Gets `sound` going, paused until `set_playing` says otherwise.
+        pub fn start(sound: AmbientSound, volume: u8) -> io::Result<Loop> {
+            let (started, started_rx) = mpsc::sync_channel(1);
+            let (stop, stop_rx) = mpsc::channel::<()>();
+            thread::spawn(move || match open() {
+                Ok((_stream, sink)) => {
+                    sink.pause();
+                    sink.set_volume(f32::from(volume) / 100.0);
+                    sink.append(ambient(sound));
+                    let _ = started.send(Ok(sink));
+                    // nothing is ever sent - this just waits for the loop to be dropped
+                    let _ = stop_rx.recv();
+                }
+                Err(e) => {
+                    let _ = started.send(Err(e));
+                }
+            });
+
+            match started_rx.recv() {
+                Ok(result) => Ok(Loop {
+                    sink: result.map_err(io::Error::other)?,
+                    _stop: stop,
+                }),
+                Err(_) => Err(io::Error::other("the sound player gave up")),
+            }
+        }
+
+        pub fn set_playing(&self, playing: bool) {
+            if playing {
+                self.sink.play();
+            } else {
+                self.sink.pause();
+            }
+        }
+    }
+
+    fn open() -> Result<(OutputStream, Sink), String> {
+        let (stream, handle) = OutputStream::try_default().map_err(|e| e.to_string())?;
+        let sink = Sink::try_new(&handle).map_err(|e| e.to_string())?;
+        Ok((stream, sink))
+    }
+
+    fn ambient(sound: AmbientSound) -> Samples {
+        match sound {
+            AmbientSound::Tick => {
+                Box::new(SamplesBuffer::new(1, SAMPLE_RATE, tick()).repeat_infinite())
+            }
+            AmbientSound::WhiteNoise => Box::new(Noise::new(false)),
+            AmbientSound::BrownNoise => Box::new(Noise::new(true)),
+        }
+    }
+
+    /// One second of clockwork - a short, sharp click and then quiet.
+    fn tick() -> Vec<f32> {
+        (0..SAMPLE_RATE)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                0.5 * (2.0 * PI * 2_000.0 * t).sin() * (-600.0 * t).exp()
+            })
+            .collect()
+    }
+
+    /// Endless white noise, or brown noise (white noise with the hiss taken out, more like a
+    /// distant waterfall).
+    struct Noise {
+        seed: u32,
+        brown: Option<f32>,
+    }
+
+    impl Noise {
+        fn new(brown: bool) -> Noise {
+            Noise {
+                seed: 0x9E37_79B9,
+                brown: if brown { Some(0.0) } else { None },
+            }
+        }
+    }
+
+    impl Iterator for Noise {
+        type Item = f32;
+
+        fn next(&mut self) -> Option<f32> {
+            // xorshift - noise doesn't need a better random number generator than this
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 17;
+            self.seed ^= self.seed << 5;
+            let white = self.seed as f32 / u32::MAX as f32 * 2.0 - 1.0;
+
+            Some(match &mut self.brown {
+                Some(last) => {
+                    *last = (*last + 0.02 * white) / 1.02;
+                    *last * 3.5
+                }
+                None => white * 0.5,
+            })
+        }
+    }
+
+    impl Source for Noise {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            1
+        }
+
+        fn sample_rate(&self) -> u32 {
+            SAMPLE_RATE
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    /// A soft two note chime, like a doorbell - each note a bell-like tone (a sine with a quieter
+    /// octave above it) that dies away.
+    fn chime() -> Vec<f32> {
+        let length = (CHIME_LENGTH * SAMPLE_RATE as f32) as usize;
+        (0..length)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                CHIME_NOTES
+                    .iter()
+                    .filter(|&&(at, _)| t >= at)
+                    .map(|&(at, pitch)| {
+                        let t = t - at;
+                        let tone =
+                            (2.0 * PI * pitch * t).sin() + 0.3 * (4.0 * PI * pitch * t).sin();
+                        0.3 * tone * (-4.0 * t).exp()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_chime() {
+            let chime = chime();
+            assert_eq!(chime.len(), 66_150);
+            assert!(chime.iter().all(|sample| sample.abs() <= 1.0));
+            assert!(chime.iter().any(|sample| sample.abs() > 0.3));
+            // rung out by the end
+            assert!(chime[chime.len() - 100..].iter().all(|s| s.abs() < 0.01));
+        }
+
+        #[test]
+        fn test_noise() {
+            for brown in &[false, true] {
+                let noise: Vec<f32> = Noise::new(*brown).take(SAMPLE_RATE as usize).collect();
+                assert!(noise.iter().all(|sample| sample.abs() <= 1.0));
+                assert!(noise.iter().any(|sample| sample.abs() > 0.1));
+            }
+        }
+
+        #[test]
+        fn test_missing_file() {
+            let mut player = Player::new(&SoundSettings {
+                work_end: Sound::File("/no/such/gong.ogg".into()),
+                ..SoundSettings::default()
+            });
+            let message = Message::phase_end(PomodoroState::Working);
+            assert!(player.notify(&message).is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sound_settings() {
+        let settings: SoundSettings =
+            toml::from_str("work_end = \"/tmp/gong.ogg\"\nvolume = 40\n").unwrap();
+        assert_eq!(settings.work_end, Sound::File("/tmp/gong.ogg".into()));
+        assert_eq!(settings.break_end, Sound::Chime);
+        assert_eq!(settings.volume, 40);
+
+        let error = toml::from_str::<SoundSettings>("volume = 110\n").unwrap_err();
+        assert!(error.to_string().contains("too loud"));
+    }
+
+    #[test]
+    fn test_ambience_only_plays_while_working() {
+        let started = |state| Event::PhaseStarted {
+            state,
+            order: 1,
+            duration: std::time::Duration::from_secs(60),
+        };
+        let mut ambience = Ambience::new(&SoundSettings::default());
+        assert!(!ambience.is_enabled());
+
+        // follow the timer switched off, then flip the switch without going near the speakers
+        let mut playing_after = |event: Event| {
+            ambience.enabled = false;
+            ambience.follow(&event).unwrap();
+            ambience.enabled = true;
+            ambience.is_playing()
+        };
+        assert!(playing_after(started(PomodoroState::Working)));
+        assert!(!playing_after(Event::Paused));
+        assert!(playing_after(Event::Resumed));
+        assert!(!playing_after(started(PomodoroState::ShortBreak)));
+    }
+
+    #[cfg(not(feature = "audio"))]
+    #[test]
+    fn test_ambience_without_audio() {
+        let mut ambience = Ambience::new(&SoundSettings::default());
+        assert!(ambience.toggle().is_err());
+        assert!(!ambience.is_enabled());
+    }
+}
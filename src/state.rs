@@ -0,0 +1,364 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::PomodoroConfig;
+use crate::sequence::{Kind, Phase, Sequence};
+
+/// Number of pomodoros in a cycle when none is configured.
+pub const DEFAULT_CYCLE_LENGTH: u32 = 4;
+
+/// Which part of the pomodoro cycle we are in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PomodoroState {
+    Working,
+    ShortBreak,
+    LongBreak,
+    #[default]
+    None,
+}
+
+impl PomodoroState {
+    /// Whether this state is one of the two breaks.
+    pub fn is_break(self) -> bool {
+        matches!(self, PomodoroState::ShortBreak | PomodoroState::LongBreak)
+    }
+
+    /// The state the way it is spelt in JSON - `working`, `short_break`, `long_break` or `none`.
+    pub fn name(self) -> &'static str {
+        match self {
+            PomodoroState::Working => "working",
+            PomodoroState::ShortBreak => "short_break",
+            PomodoroState::LongBreak => "long_break",
+            PomodoroState::None => "none",
+        }
+    }
+}
+
+/// A simple state tracker that keeps track of the pomodoro state, where we are in the sequence of
+/// phases (the one running, or the last one to run between phases - None if we haven't begun our
+/// first pomodoro yet), and when the current phase was started at.  (Started at is None between
+/// pomodoros).  Time spent paused is kept apart from the running time so it never counts as focus
+/// time.
+///
+/// The sequence is the classic cycle of work periods and short breaks ending in a long break,
+/// unless a custom one is set.  Either way the order is counted in work periods - 1 to the cycle
+/// length - with each break sharing the order of the work period before it.
+///
+/// The tracker never reads the system clock itself - every method that cares about time is handed
+/// the current instant, which keeps it usable from any time source.
+#[derive(Debug)]
+pub struct StateTracker {
+    sequence: Sequence,
+    position: Option<usize>,
+    current_state: PomodoroState,
+    started_at: Option<Instant>,
+    paused_at: Option<Instant>,
+    paused_for: Duration,
+}
+
+impl Default for StateTracker {
+    fn default() -> StateTracker {
+        StateTracker::new()
+    }
+}
+
+impl StateTracker {
+    /// A tracker with the cycle the default settings make up - 4 pomodoros before a long break.
+    pub fn new() -> StateTracker {
+        StateTracker::with_sequence(PomodoroConfig::default().cycle())
+    }
+
+    /// A tracker that works through `sequence`.
+    pub fn with_sequence(sequence: Sequence) -> StateTracker {
+        StateTracker {
+            sequence,
+            position: None,
+            current_state: PomodoroState::None,
+            started_at: None,
+            paused_at: None,
+            paused_for: Duration::from_secs(0),
+        }
+    }
+
+    /// Switches to working through `sequence`, carrying on from the same place in it (or from the
+    /// top, if it is too short to have one).
+    pub(crate) fn set_sequence(&mut self, sequence: Sequence) {
+        self.position = self
+            .position
+            .filter(|&position| position < sequence.phases().len());
+        self.sequence = sequence;
+    }
+
+    /// The sequence being worked through.
+    pub fn sequence(&self) -> &Sequence {
+        &self.sequence
+    }
+
+    /// The phase running, or the last one to run if we are between phases.
+    pub fn phase(&self) -> Option<&Phase> {
+        self.position
+            .and_then(|position| self.sequence.phases().get(position))
+    }
+
+    pub(crate) fn position(&self) -> Option<usize> {
+        self.position
+    }
+
+    /// Where the phase after the current one sits in the sequence.
+    fn next_position(&self) -> usize {
+        match self.position {
+            Some(position) => (position + 1) % self.sequence.phases().len(),
+            None => 0,
+        }
+    }
+
+    fn is_work(&self, position: usize) -> bool {
+        self.sequence.phases()[position].kind == Kind::Work
+    }
+
+    /// Moves on to the next work period in the sequence.
+    pub(crate) fn increment_cycle(&mut self) {
+        let len = self.sequence.phases().len();
+        let next = self.next_position();
+        self.position = (0..len)
+            .map(|step| (next + step) % len)
+            .find(|&position| self.is_work(position));
+    }
+
+    /// Moves back to the work period before the current pomodoro, so that the next
+    /// `increment_cycle` lands on the current pomodoro again.
+    pub(crate) fn decrement_cycle(&mut self) {
+        let current = self
+            .position
+            .and_then(|position| (0..=position).rev().find(|&p| self.is_work(p)));
+        self.position = current.and_then(|current| (0..current).rev().find(|&p| self.is_work(p)));
+    }
+
+    /// Gets the current order we are at within a pomdoro cycle.
+    pub fn get_order(&self) -> Option<u32> {
+        let position = self.position?;
+        let works = (0..=position).filter(|&p| self.is_work(p)).count();
+        Some(works as u32)
+    }
+
+    /// Gets the number of pomodoros in a cycle.
+    pub fn get_cycle_length(&self) -> u32 {
+        (0..self.sequence.phases().len())
+            .filter(|&p| self.is_work(p))
+            .count() as u32
+    }
+
+    /// Gets the current pomodoro state.
+    pub fn get_state(&self) -> PomodoroState {
+        self.current_state
+    }
+
+    /// Sets the current work state by storing the current time, setting our pomodoro state to
+    /// PomodoroState::Working, and then moving on to the next work period in the sequence.
+    pub fn set_work_state(&mut self, now: Instant) {
+        self.start_phase(now);
+        self.current_state = PomodoroState::Working;
+        self.increment_cycle();
+    }
+
+    /// The state of the phase that comes next in the sequence.
+    pub fn next_state(&self) -> PomodoroState {
+        self.sequence.phases()[self.next_position()].kind.state()
+    }
+
+    /// The break that follows the current phase (ShortBreak, LongBreak, or None before the first
+    /// pomodoro or when work comes next).
+    pub fn next_break(&self) -> PomodoroState {
+        match self.position {
+            Some(_) if self.next_state().is_break() => self.next_state(),
+            _ => PomodoroState::None,
+        }
+    }
+
+    /// Sets the break state (ShortBreak, LongBreak, or None) and restarts the phase timer.
+    pub fn set_break_state(&mut self, now: Instant) {
+        let break_state = self.next_break();
+        self.start_phase(now);
+        if break_state != PomodoroState::None {
+            self.position = Some(self.next_position());
+        }
+        self.current_state = break_state;
+    }
+
+    /// Goes back to the phase at `position` in the sequence with the phase timer restarted - for
+    /// picking a phase that has already ended back up.
+    pub(crate) fn reopen(&mut self, position: usize, now: Instant) {
+        self.start_phase(now);
+        self.current_state = self.sequence.phases()[position].kind.state();
+        self.position = Some(position);
+    }
+
+    /// Drops back to the state between pomodoros.  The place in the sequence is kept so the next
+    /// phase carries on from here.
+    pub fn set_idle_state(&mut self) {
+        self.current_state = PomodoroState::None;
+        self.started_at = None;
+        self.paused_at = None;
+    }
+
+    fn start_phase(&mut self, now: Instant) {
+        self.started_at = Some(now);
+        self.paused_at = None;
+        self.paused_for = Duration::from_secs(0);
+    }
+
+    /// Marks the current phase as paused.  Pausing twice in a row is a no-op.
+    pub fn pause(&mut self, now: Instant) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(now);
+        }
+    }
+
+    /// Resumes a paused phase.  The time spent paused is added to the paused total and pushed
+    /// onto `started_at`, so the elapsed running time picks up exactly where it stopped.
+    pub fn resume(&mut self, now: Instant) {
+        if let Some(paused_at) = self.paused_at.take() {
+            let paused = now.saturating_duration_since(paused_at);
+            self.paused_for += paused;
+            self.started_at = self.started_at.map(|started| started + paused);
+        }
+    }
+
+    /// Whether the current phase is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// When the current phase started, shifted forward by any time spent paused.
+    pub fn get_started_at(&self) -> Option<Instant> {
+        self.started_at
+    }
+
+    /// Total time spent paused since the current phase started.
+    pub fn get_paused_time(&self, now: Instant) -> Duration {
+        match self.paused_at {
+            Some(paused_at) => self.paused_for + now.saturating_duration_since(paused_at),
+            None => self.paused_for,
+        }
+    }
+
+    /// Time the current phase has actually been running for, leaving out pauses.
+    pub fn get_running_time(&self, now: Instant) -> Duration {
+        let until = self.paused_at.unwrap_or(now);
+        match self.started_at {
+            Some(started_at) => until.saturating_duration_since(started_at),
+            None => Duration::from_secs(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_cycle() {
+        let mut pstate = StateTracker::new();
+        pstate.increment_cycle();
+        assert_eq!(pstate.get_order().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_increment_cycle() {
+        let mut pstate = StateTracker::new();
+        pstate.increment_cycle();
+        pstate.increment_cycle();
+        assert_eq!(pstate.get_order().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_cycle_loop() {
+        let mut pstate = StateTracker::new();
+        pstate.increment_cycle();
+        pstate.increment_cycle();
+        pstate.increment_cycle();
+        pstate.increment_cycle();
+        pstate.increment_cycle();
+        assert_eq!(pstate.get_order(), Some(1));
+    }
+
+    #[test]
+    fn test_custom_cycle_length() {
+        let now = Instant::now();
+        let config = PomodoroConfig {
+            cycle_length: 3,
+            ..PomodoroConfig::default()
+        };
+        let mut pstate = StateTracker::with_sequence(config.cycle());
+        pstate.set_work_state(now);
+        pstate.set_break_state(now);
+        assert_eq!(pstate.get_state(), PomodoroState::ShortBreak);
+
+        pstate.set_work_state(now);
+        pstate.set_work_state(now);
+        pstate.set_break_state(now);
+        assert_eq!(pstate.get_order(), Some(3));
+        assert_eq!(pstate.get_state(), PomodoroState::LongBreak);
+
+        pstate.set_work_state(now);
+        assert_eq!(pstate.get_order(), Some(1));
+    }
+
+    #[test]
+    fn test_custom_sequence() {
+        let now = Instant::now();
+        let mut pstate = StateTracker::with_sequence("90w 30w 15b 60B".parse().unwrap());
+        assert_eq!(pstate.get_cycle_length(), 2);
+        assert_eq!(pstate.next_state(), PomodoroState::Working);
+
+        pstate.set_work_state(now);
+        assert_eq!(pstate.phase().unwrap().duration, 90);
+        assert_eq!(pstate.next_break(), PomodoroState::None);
+
+        pstate.set_work_state(now);
+        assert_eq!(pstate.get_order(), Some(2));
+        pstate.set_break_state(now);
+        assert_eq!(pstate.get_state(), PomodoroState::ShortBreak);
+        assert_eq!(pstate.next_break(), PomodoroState::LongBreak);
+        pstate.set_break_state(now);
+        assert_eq!(pstate.get_state(), PomodoroState::LongBreak);
+        assert_eq!(pstate.get_order(), Some(2));
+
+        // starting the pomodoro over goes back to the top of its work period
+        pstate.decrement_cycle();
+        pstate.set_work_state(now);
+        assert_eq!(pstate.phase().unwrap().duration, 30);
+
+        pstate.set_idle_state();
+        pstate.set_work_state(now);
+        assert_eq!(pstate.get_order(), Some(1));
+    }
+
+    #[test]
+    fn test_pause_shifts_start() {
+        let now = Instant::now();
+        let mut pstate = StateTracker::new();
+        pstate.set_work_state(now);
+
+        pstate.pause(now + Duration::from_secs(5));
+        assert!(pstate.is_paused());
+        assert_eq!(
+            pstate.get_running_time(now + Duration::from_secs(60)),
+            Duration::from_secs(5)
+        );
+        pstate.resume(now + Duration::from_secs(65));
+
+        assert!(!pstate.is_paused());
+        assert_eq!(pstate.get_paused_time(now), Duration::from_secs(60));
+        assert_eq!(
+            pstate.get_started_at().unwrap() - now,
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            pstate.get_running_time(now + Duration::from_secs(70)),
+            Duration::from_secs(10)
+        );
+    }
+}
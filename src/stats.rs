@@ -0,0 +1,366 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::engine::Outcome;
+use crate::history::{History, HistoryEntry};
+use crate::state::PomodoroState;
+
+/// Flags for `pomodoro stats`.
+#[derive(StructOpt, Debug)]
+pub struct StatsOptions {
+    #[structopt(long = "since", parse(try_from_str = "parse_date"))]
+    /// Only count pomodoros started on or after this day (YYYY-MM-DD).
+    pub since: Option<NaiveDate>,
+
+    #[structopt(long = "until", parse(try_from_str = "parse_date"))]
+    /// Only count pomodoros started on or before this day (YYYY-MM-DD).
+    pub until: Option<NaiveDate>,
+
+    #[structopt(
+        long = "by",
+        default_value = "day",
+        raw(possible_values = r#"&["day", "week", "month"]"#)
+    )]
+    /// Groups the summary by day, week or month.
+    pub period: Period,
+
+    #[structopt(long = "json")]
+    /// Prints the summary as JSON instead of a table.
+    pub json: bool,
+}
+
+/// How `pomodoro stats` groups the history.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    /// The label of the day, ISO week or month a date falls in.
+    fn label(self, date: NaiveDate) -> String {
+        match self {
+            Period::Day => date.format("%Y-%m-%d").to_string(),
+            Period::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Month => date.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Period, String> {
+        match value {
+            "day" => Ok(Period::Day),
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            _ => Err(format!("'{}' isn't one of day, week or month", value)),
+        }
+    }
+}
+
+/// How one day, week or month went.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub period: String,
+    /// Work periods that ran all the way down.
+    pub pomodoros: u32,
+    /// Minutes spent working, pauses left out.
+    pub focus_minutes: u64,
+    /// Work periods that were reset, quit or skipped part way through.
+    pub interruptions: u32,
+    /// Most pomodoros finished back to back without an interruption.
+    pub longest_streak: u32,
+    /// Work periods that ran into overtime before you took your break.
+    pub overruns: u32,
+    #[serde(skip)]
+    focus_secs: u64,
+    #[serde(skip)]
+    streak: u32,
+}
+
+impl Summary {
+    fn new(period: String) -> Summary {
+        Summary {
+            period,
+            ..Summary::default()
+        }
+    }
+
+    fn add(&mut self, entry: &HistoryEntry) {
+        if entry.phase != PomodoroState::Working {
+            return;
+        }
+
+        self.focus_secs += entry.actual_secs + entry.overtime_secs;
+        self.focus_minutes = self.focus_secs / 60;
+        if entry.overtime_secs > 0 {
+            self.overruns += 1;
+        }
+
+        // a snooze carries on the pomodoro before it rather than being one of its own
+        if entry.snoozed {
+            return;
+        }
+
+        match entry.outcome {
+            Outcome::Completed => {
+                self.pomodoros += 1;
+                self.streak += 1;
+                self.longest_streak = self.longest_streak.max(self.streak);
+            }
+            _ => {
+                self.interruptions += 1;
+                self.streak = 0;
+            }
+        }
+    }
+}
+
+/// Groups the work periods in `entries` by `period`, oldest first, keeping only those started
+/// between `since` and `until` (both inclusive).
+pub fn summarize(
+    entries: &[HistoryEntry],
+    period: Period,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Vec<Summary> {
+    let mut entries = entries
+        .iter()
+        .filter(|entry| {
+            let date = entry.started_at.date_naive();
+            since.is_none_or(|since| date >= since) && until.is_none_or(|until| date <= until)
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.started_at);
+
+    let mut summaries: Vec<Summary> = vec![];
+    for entry in entries {
+        let label = period.label(entry.started_at.date_naive());
+        match summaries.last_mut() {
+            Some(summary) if summary.period == label => summary.add(entry),
+            _ => {
+                let mut summary = Summary::new(label);
+                summary.add(entry);
+                summaries.push(summary);
+            }
+        }
+    }
+
+    summaries
+}
+
+/// Runs `pomodoro stats` - reads the history and prints how it went.
+pub fn run(options: &StatsOptions) -> Result<(), Box<dyn Error>> {
+    let entries = History::open_default()?.entries()?;
+    let summaries = summarize(&entries, options.period, options.since, options.until);
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&summaries)?);
+    } else {
+        print!("{}", Table(&summaries));
+    }
+
+    Ok(())
+}
+
+/// Parses a `YYYY-MM-DD` date.
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("'{}' isn't a date like 2019-01-31", value))
+}
+
+/// Lays the summaries out as a table for the terminal.
+struct Table<'a>(&'a [Summary]);
+
+impl<'a> fmt::Display for Table<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return writeln!(f, "No pomodoros recorded yet.");
+        }
+
+        writeln!(
+            f,
+            "{:<10}  {:>9}  {:>7}  {:>13}  {:>14}  {:>8}",
+            "period", "pomodoros", "focus", "interruptions", "longest streak", "overruns"
+        )?;
+        for summary in self.0 {
+            writeln!(
+                f,
+                "{:<10}  {:>9}  {:>7}  {:>13}  {:>14}  {:>8}",
+                summary.period,
+                summary.pomodoros,
+                format_minutes(summary.focus_minutes),
+                summary.interruptions,
+                summary.longest_streak,
+                summary.overruns,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats minutes as `1h05m`, or just `25m` under an hour.
+fn format_minutes(minutes: u64) -> String {
+    if minutes >= 60 {
+        format!("{}h{:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PomodoroConfig;
+    use crate::engine::{Engine, Event, SNOOZE};
+    use crate::time::{ManualClock, TimeSource};
+    use chrono::{Local, TimeZone};
+    use std::time::Duration;
+
+    fn entry(day: u32, hour: u32, phase: PomodoroState, outcome: Outcome) -> HistoryEntry {
+        HistoryEntry {
+            started_at: Local.with_ymd_and_hms(2019, 3, day, hour, 0, 0).unwrap(),
+            phase,
+            order: 1,
+            planned_secs: 25 * 60,
+            extended_secs: 0,
+            snoozed: false,
+            actual_secs: if outcome == Outcome::Completed {
+                1500
+            } else {
+                600
+            },
+            paused_secs: 0,
+            overtime_secs: 0,
+            outcome,
+        }
+    }
+
+    fn history() -> Vec<HistoryEntry> {
+        use Outcome::*;
+        use PomodoroState::*;
+
+        vec![
+            entry(4, 9, Working, Completed),
+            entry(4, 10, ShortBreak, Completed),
+            entry(4, 11, Working, Completed),
+            entry(4, 12, Working, Reset),
+            entry(4, 13, Working, Completed),
+            entry(5, 9, Working, Quit),
+            entry(11, 9, Working, Completed),
+        ]
+    }
+
+    #[test]
+    fn test_summarize_by_day() {
+        let summaries = summarize(&history(), Period::Day, None, None);
+
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0].period, "2019-03-04");
+        assert_eq!(summaries[0].pomodoros, 3);
+        assert_eq!(summaries[0].focus_minutes, 25 * 3 + 10);
+        assert_eq!(summaries[0].interruptions, 1);
+        assert_eq!(summaries[0].longest_streak, 2);
+
+        assert_eq!(summaries[1].period, "2019-03-05");
+        assert_eq!(summaries[1].pomodoros, 0);
+        assert_eq!(summaries[1].interruptions, 1);
+    }
+
+    #[test]
+    fn test_snoozes_and_overruns() {
+        // a pomodoro that runs down and gets snoozed, straight from the engine
+        let config = PomodoroConfig {
+            work_time: 25,
+            short_break_time: 5,
+            ..PomodoroConfig::default()
+        };
+        let mut engine = Engine::new(&config);
+        let time = ManualClock::new();
+        let mut events = engine.start_work(time.now());
+        time.advance(Duration::from_secs(25 * 60));
+        events.extend(engine.tick(time.now()));
+        time.advance(Duration::from_secs(10));
+        events.extend(engine.snooze(time.now(), SNOOZE));
+        time.advance(SNOOZE);
+        events.extend(engine.tick(time.now()));
+
+        let ended_at = Local.with_ymd_and_hms(2019, 3, 4, 10, 0, 0).unwrap();
+        let mut entries = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::PhaseEnded(summary) => Some(HistoryEntry::from_summary(summary, ended_at)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[2].snoozed);
+
+        let mut overran = entry(4, 11, PomodoroState::Working, Outcome::Completed);
+        overran.overtime_secs = 180;
+        entries.push(overran);
+
+        let summaries = summarize(&entries, Period::Day, None, None);
+        assert_eq!(summaries[0].pomodoros, 2);
+        assert_eq!(summaries[0].longest_streak, 2);
+        assert_eq!(summaries[0].focus_minutes, 25 + 5 + 25 + 3);
+        assert_eq!(summaries[0].interruptions, 0);
+        assert_eq!(summaries[0].overruns, 1);
+    }
+
+    #[test]
+    fn test_summarize_by_week_and_month() {
+        let weeks = summarize(&history(), Period::Week, None, None);
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(weeks[0].period, "2019-W10");
+        assert_eq!(weeks[0].pomodoros, 3);
+        assert_eq!(weeks[1].period, "2019-W11");
+
+        let months = summarize(&history(), Period::Month, None, None);
+        assert_eq!(months.len(), 1);
+        assert_eq!(months[0].period, "2019-03");
+        assert_eq!(months[0].pomodoros, 4);
+        assert_eq!(months[0].interruptions, 2);
+    }
+
+    #[test]
+    fn test_summarize_since_until() {
+        let since = parse_date("2019-03-05").ok();
+        let until = parse_date("2019-03-10").ok();
+        let summaries = summarize(&history(), Period::Day, since, until);
+
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].period, "2019-03-05");
+        assert!(parse_date("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_summary_json() {
+        let summaries = summarize(&history(), Period::Month, None, None);
+        let json = serde_json::to_value(&summaries).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "period": "2019-03",
+                "pomodoros": 4,
+                "focus_minutes": 120,
+                "interruptions": 2,
+                "longest_streak": 2,
+                "overruns": 0,
+            }])
+        );
+    }
+}
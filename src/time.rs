@@ -0,0 +1,89 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Where the timer gets the current time from, and how it waits for time to pass.  The real
+/// timer uses [`SystemClock`]; tests use [`ManualClock`] so a whole cycle runs in no time at all.
+pub trait TimeSource {
+    /// The current instant.
+    fn now(&self) -> Instant;
+
+    /// Blocks until `duration` has passed.
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real clock - `Instant::now` and `thread::sleep`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A fake clock that only moves when told to.  Sleeping on it moves it forward instantly, so
+/// anything waiting on the timer finishes right away.  Clones share the same time, so a test can
+/// keep one handle and give the other to whatever it is driving.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Rc<Cell<Duration>>,
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl ManualClock {
+    /// A fake clock starting at the current instant.
+    pub fn new() -> ManualClock {
+        ManualClock {
+            start: Instant::now(),
+            elapsed: Rc::new(Cell::new(Duration::from_secs(0))),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.set(self.elapsed.get() + duration);
+    }
+
+    /// How far the clock has moved since it was made.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed.get()
+    }
+}
+
+impl TimeSource for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_sleep() {
+        let time = ManualClock::new();
+        let handle = time.clone();
+        let start = time.now();
+
+        time.sleep(Duration::from_secs(90));
+        assert_eq!(handle.now() - start, Duration::from_secs(90));
+        assert_eq!(handle.elapsed(), Duration::from_secs(90));
+    }
+}
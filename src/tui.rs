@@ -0,0 +1,1422 @@
+use std::io;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use termion::raw::IntoRawMode;
+use termion::{clear, color, cursor, style};
+
+use crate::config::PomodoroConfig;
+use crate::control::{self, ControlServer, Request};
+use crate::engine::{Engine, Event, Outcome, SNOOZE};
+use crate::error::PomodoroError;
+use crate::history::{History, HistoryEntry};
+use crate::hooks::Hooks;
+use crate::notify::{Message, Notifier, Notifiers};
+use crate::sound::Ambience;
+use crate::state::PomodoroState;
+use crate::time::{SystemClock, TimeSource};
+
+/// The pomodoro menu.
+const POMODORO_MENU: &str = "
+╔═════════════════╗
+║───┬ Pomodoro────║
+║ s ┆ start next  ║
+║ q ┆ quit        ║
+╚═══╧═════════════╝";
+
+/// The menu between a work period and its break, when the break waits to be started.
+const BREAK_MENU: &str = "
+╔═════════════════╗
+║───┬ Break time──║
+║ s ┆ start break ║
+║ + ┆ 5 more mins ║
+║ q ┆ quit        ║
+╚═══╧═════════════╝";
+
+/// Initial pomodoro welcome menu.
+pub const POMODORO_START_PROMPT: &str = "
+╔══════════════════════════════╗
+║──Start your first Pomodoro!─-║
+║──────────────────────────────║
+║ s ┆ start    Press s         ║
+║ q ┆ quit     to start!       ║
+║ r ┆ reset                    ║
+║ p ┆ pause                    ║
+╚═══╧══════════════════════════╝";
+
+/// Controls layout always on screen when clock is rolling.
+pub const CONTROLS: &str = "
+------controls------
+ q    ~ end current
+ r    ~ reset
+ p    ~ pause/resume
+ n    ~ skip to next
+ +    ~ 5 more mins
+ t    ~ ticking on/off
+";
+
+/// Shown in place of the controls while a phase is running over.
+const OVERTIME_HELP: &str = "
+--------------------
+ any key ~ move on
+ +       ~ 5 more mins
+ q       ~ end current
+";
+
+/// One line stand-in for the menus when the terminal is too small to draw them.
+const COMPACT_MENU: &str = "Pomodoro: s start, q quit";
+
+/// Smallest terminal the full clock, work count, controls and warning line fit in.  Anything
+/// smaller gets the compact one line layout instead.
+const MIN_WIDTH: u16 = 41;
+const MIN_HEIGHT: u16 = 23;
+
+/// How long a warning that the phase is nearly over stays up in the clock box.
+const HEADS_UP_FOR: Duration = Duration::from_secs(5);
+
+/// How often we look for keypresses while the clock is rolling.
+const INPUT_POLL: Duration = Duration::from_millis(50);
+
+/// This struct represents a pomodoro session - which is from the start of running the application
+/// until you terminate it. Between that time this struct will keep track of the height and width
+/// of the terminal window (updated whenever it is resized), and keep a lock on stdin and stdout so
+/// we can draw to the screen, and also accept async input from the user. Lastly, this session
+/// holds the timer engine, which does all of the actual time keeping - the session just draws
+/// whatever the engine tells it happened - the time source it reads the time from, (optionally)
+/// the history that every finished, reset or quit phase gets written to, and (optionally) the
+/// control socket other programs can drive the timer through.
+pub struct PomodoroSession<R, W, T = SystemClock> {
+    stdin: R,
+    stdout: W,
+    width: u16,
+    height: u16,
+    engine: Engine,
+    hooks: Hooks,
+    notifier: Box<dyn Notifier>,
+    ambience: Ambience,
+    time: T,
+    history: Option<History>,
+    menu: Option<&'static str>,
+    warning: Option<String>,
+    heads_up: Option<(String, Instant)>,
+    resized: Option<Arc<AtomicBool>>,
+    control: Option<ControlServer>,
+    exiting: bool,
+}
+
+impl<R: Read, W: Write> PomodoroSession<R, W> {
+    /// Builds a session drawing to `stdout` and listening on `stdin`, with a terminal of the given
+    /// size.
+    pub fn new(
+        stdin: R,
+        stdout: W,
+        width: u16,
+        height: u16,
+        config: &PomodoroConfig,
+    ) -> PomodoroSession<R, W> {
+        PomodoroSession::with_time_source(stdin, stdout, width, height, config, SystemClock)
+    }
+}
+
+impl<R: Read, W: Write, T: TimeSource> PomodoroSession<R, W, T> {
+    /// Builds a session that reads the time from (and sleeps on) `time` instead of the system
+    /// clock.
+    pub fn with_time_source(
+        stdin: R,
+        stdout: W,
+        width: u16,
+        height: u16,
+        config: &PomodoroConfig,
+        time: T,
+    ) -> PomodoroSession<R, W, T> {
+        PomodoroSession {
+            stdin,
+            stdout,
+            width,
+            height,
+            engine: Engine::new(config),
+            hooks: config.hooks.clone(),
+            notifier: Box::new(Notifiers::from_settings(&config.notify)),
+            ambience: Ambience::new(&config.notify.sound),
+            time,
+            history: None,
+            menu: None,
+            warning: None,
+            heads_up: None,
+            resized: None,
+            control: None,
+            exiting: false,
+        }
+    }
+
+    /// Writes every phase that ends from here on to `history`.
+    pub fn record_to(&mut self, history: History) {
+        self.history = Some(history);
+    }
+
+    /// Sends notifications through `notifier` instead of the backends picked in the config.
+    pub fn notify_with<N: Notifier + 'static>(&mut self, notifier: N) {
+        self.notifier = Box::new(notifier);
+    }
+
+    /// Answers requests coming in on `control` from here on.
+    pub fn listen_on(&mut self, control: ControlServer) {
+        self.control = Some(control);
+    }
+
+    /// Picks up the new terminal size and repaints whenever `resized` gets set - `init` hooks it
+    /// up to SIGWINCH.
+    pub fn watch_resize(&mut self, resized: Arc<AtomicBool>) {
+        self.resized = Some(resized);
+    }
+
+    /// Switches to a terminal of the given size and repaints whatever is on screen.
+    pub fn resize(&mut self, width: u16, height: u16) -> Result<(), PomodoroError> {
+        self.width = width;
+        self.height = height;
+
+        write!(self.stdout, "{}", clear::All)?;
+        if self.engine.is_running() {
+            self.draw_screen()?;
+        } else if let Some(menu) = self.menu {
+            self.draw_menu(menu)?;
+        }
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    /// The terminal size the session is drawing for.
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    fn check_resize(&mut self) -> Result<(), PomodoroError> {
+        let resized = match &self.resized {
+            Some(resized) => resized.swap(false, Ordering::Relaxed),
+            None => false,
+        };
+
+        if resized {
+            let (width, height) = termion::terminal_size()?;
+            self.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    /// The timer engine behind the session.
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// The warning on screen, if something (like a notification) has failed along the way.
+    pub fn warning(&self) -> Option<&str> {
+        self.warning.as_deref()
+    }
+
+    /// Puts `warning` up along the bottom of the screen.
+    pub fn warn<S: Into<String>>(&mut self, warning: S) {
+        self.warning = Some(warning.into());
+    }
+
+    fn start(&mut self) -> Result<(), PomodoroError> {
+        write!(self.stdout, "{}", cursor::Hide)?;
+        self.display_menu(Some(POMODORO_START_PROMPT))
+    }
+
+    fn begin_cycle(&mut self) -> Result<(), PomodoroError> {
+        self.start_work()?;
+        self.display_menu(None)
+    }
+
+    /// Call a start to a work cycle, or the break waiting to be started (unless a phase was just
+    /// started over the control socket, in which case we pick up its countdown).
+    pub fn start_work(&mut self) -> Result<(), PomodoroError> {
+        if !self.engine.is_running() {
+            let events = self.engine.start_next(self.time.now());
+            self.handle_events(events)?;
+        }
+        self.countdown()
+    }
+
+    /// Reset the current pomodoro back to the top of its work period.
+    pub fn reset_current_pomodoro(&mut self) -> Result<(), PomodoroError> {
+        let events = self.engine.reset(self.time.now());
+        self.handle_events(events)
+    }
+
+    /// Pauses the running clock, or resumes it if it is already paused.
+    pub fn toggle_pause(&mut self) -> Result<(), PomodoroError> {
+        let now = self.time.now();
+        let events = if self.engine.is_paused() {
+            self.engine.resume(now)
+        } else {
+            self.engine.pause(now)
+        };
+        self.handle_events(events)
+    }
+
+    /// Ends the running phase early and moves on to the next one.
+    pub fn skip_phase(&mut self) -> Result<(), PomodoroError> {
+        let events = self.engine.skip(self.time.now());
+        self.handle_events(events)
+    }
+
+    /// Gives the running phase five more minutes - or, straight after a phase runs down, brings
+    /// that one back for five more.
+    pub fn extend_phase(&mut self) -> Result<(), PomodoroError> {
+        let events = self.engine.extend(self.time.now(), SNOOZE);
+        self.handle_events(events)
+    }
+
+    /// Moves on from a phase that is running over.
+    pub fn acknowledge(&mut self) -> Result<(), PomodoroError> {
+        let events = self.engine.acknowledge(self.time.now());
+        self.handle_events(events)
+    }
+
+    /// Brings the phase that last ran down back for five more minutes.
+    pub fn snooze_phase(&mut self) -> Result<(), PomodoroError> {
+        let events = self.engine.snooze(self.time.now(), SNOOZE);
+        self.handle_events(events)
+    }
+
+    /// Switches the ticking (or whichever ambient loop is set) on or off.
+    fn toggle_ambience(&mut self) -> Result<(), PomodoroError> {
+        if let Err(e) = self.ambience.toggle() {
+            self.warning = Some(format!("couldn't play the ambient sound: {}", e));
+            self.draw_warning()?;
+            self.stdout.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Drives the engine until the current work period and its break are over (or the user bails
+    /// out) - and on into the next ones, for as long as they start on their own.  We wake up every
+    /// few milliseconds to listen for commands, and otherwise only when the engine says the clock
+    /// is due to change.
+    pub fn countdown(&mut self) -> Result<(), PomodoroError> {
+        while self.engine.is_running() || self.engine.starts_at().is_some() {
+            self.check_resize()?;
+
+            let command = if self.engine.is_overtime() {
+                self.overtime_listen()?
+            } else if self.engine.is_running() {
+                self.async_command_listen()?
+            } else {
+                self.grace_listen()?
+            };
+            let command = match command {
+                Command::None => self.poll_background()?,
+                command => command,
+            };
+
+            match command {
+                Command::Hold => {
+                    self.engine.hold();
+                }
+                Command::Quit => {
+                    let now = self.time.now();
+                    let events = self.engine.stop(now);
+                    return self.handle_events(events);
+                }
+                Command::Reset => self.reset_current_pomodoro()?,
+                Command::Pause => self.toggle_pause()?,
+                Command::Skip => self.skip_phase()?,
+                Command::Extend => self.extend_phase()?,
+                Command::Snooze => self.snooze_phase()?,
+                Command::Acknowledge => self.acknowledge()?,
+                Command::Ambience => self.toggle_ambience()?,
+                _ => (),
+            }
+
+            let now = self.time.now();
+            let events = self.engine.tick(now);
+            self.handle_events(events)?;
+
+            let wait = match self.engine.next_tick_at(now) {
+                Some(next) => next.saturating_duration_since(now).min(INPUT_POLL),
+                None => INPUT_POLL,
+            };
+            self.time.sleep(wait);
+        }
+
+        Ok(())
+    }
+
+    /// Picks up whatever has come in from outside the terminal - a request on the control
+    /// socket, or snooze picked on a notification.
+    fn poll_background(&mut self) -> Result<Command, PomodoroError> {
+        match self.poll_control()? {
+            Command::None if self.notifier.snoozed() => Ok(Command::Snooze),
+            command => Ok(command),
+        }
+    }
+
+    /// Answers the next request waiting on the control socket, if there is one.  Anything the
+    /// session loop still has to act on (starting the countdown for a pomodoro started remotely,
+    /// or shutting down) is handed back as a command.
+    fn poll_control(&mut self) -> Result<Command, PomodoroError> {
+        let incoming = match &mut self.control {
+            Some(control) => control.accept().map_err(PomodoroError::Control)?,
+            None => None,
+        };
+        let incoming = match incoming {
+            Some(incoming) => incoming,
+            None => return Ok(Command::None),
+        };
+
+        let now = self.time.now();
+        let result = incoming.request.apply(&mut self.engine, now);
+        let command = match (&incoming.request, &result) {
+            (Request::Start { .. }, Ok(_)) | (Request::Extend, Ok(_)) => Command::Start,
+            (Request::Quit, _) => {
+                self.exiting = true;
+                Command::Quit
+            }
+            _ => Command::None,
+        };
+
+        let error = match result {
+            Ok(events) => {
+                self.handle_events(events)?;
+                None
+            }
+            Err(error) => Some(error),
+        };
+        incoming.answer(error, &self.engine);
+
+        Ok(command)
+    }
+
+    /// Reacts to whatever the engine reports - running the hooks, redrawing the screen, sending
+    /// the notifications at the end of each phase and keeping the history up to date.  A
+    /// hook that can't be started or a notification that can't be sent is shown as a warning (with
+    /// a terminal bell for the notification, so the end of the phase still gets noticed) rather
+    /// than treated as an error.
+    pub fn handle_events(&mut self, events: Vec<Event>) -> Result<(), PomodoroError> {
+        for event in events {
+            if let Err(e) = self.hooks.fire(&event) {
+                self.warning = Some(format!("couldn't run a hook: {}", e));
+            }
+            if let Err(e) = self.ambience.follow(&event) {
+                self.warning = Some(format!("couldn't play the ambient sound: {}", e));
+            }
+
+            match event {
+                Event::PhaseStarted { .. } => {
+                    write!(self.stdout, "{}", clear::All)?;
+                    self.draw_screen()?;
+                }
+                Event::Tick { .. } | Event::Elapsed { .. } | Event::Resumed => {
+                    self.draw_screen()?
+                }
+                Event::Warning { state, remaining } => {
+                    let message = Message::warning(state, remaining);
+                    self.heads_up = Some((message.summary.clone(), self.time.now()));
+                    self.send(&message)?;
+                    self.draw_screen()?;
+                }
+                Event::Extended { by, .. } => {
+                    let heads_up = format!("+{} minutes", by.as_secs() / 60);
+                    self.heads_up = Some((heads_up, self.time.now()));
+                    if self.engine.is_paused() {
+                        self.draw_paused_screen()?;
+                    } else {
+                        self.draw_screen()?;
+                    }
+                }
+                Event::Overtime { .. } => self.draw_screen()?,
+                Event::Nudge { state, over } => {
+                    // the first nudge comes as the phase runs down, and the screen changes over
+                    if over == Duration::from_secs(0) {
+                        write!(self.stdout, "{}", clear::All)?;
+                    }
+                    self.send(&Message::nudge(state, over))?;
+                    self.draw_screen()?;
+                }
+                Event::Paused => self.draw_paused_screen()?,
+                Event::Grace { next, remaining } => self.draw_grace_screen(next, remaining)?,
+                Event::PhaseEnded(summary) => {
+                    self.heads_up = None;
+                    // the screen changes over once as the grace period starts, not on every tick
+                    if self.engine.starts_at().is_some() {
+                        write!(self.stdout, "{}", clear::All)?;
+                    }
+                    // a phase that ran over has been nudged about already
+                    if summary.outcome == Outcome::Completed && summary.overtime.is_none() {
+                        self.send(&Message::phase_end(summary.state))?;
+                    }
+                    if let Some(history) = &self.history {
+                        let entry = HistoryEntry::from_summary(&summary, Local::now());
+                        history.record(&entry).map_err(PomodoroError::History)?;
+                    }
+                }
+            }
+        }
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    /// Sends `message` out through the notifier.  If that fails it is put up as a warning
+    /// instead, with a terminal bell so it still gets noticed.
+    fn send(&mut self, message: &Message) -> Result<(), PomodoroError> {
+        match self.notifier.notify(message) {
+            Ok(()) => self.warning = None,
+            Err(e) => {
+                write!(self.stdout, "\x07")?;
+                self.warning = Some(e.to_string());
+            }
+        }
+        self.draw_warning()
+    }
+
+    /// What the clock box says - `title`, unless a warning that the phase is nearly over has
+    /// just gone off.
+    fn title(&self, title: &str) -> String {
+        match &self.heads_up {
+            Some((heads_up, at))
+                if self.time.now().saturating_duration_since(*at) < HEADS_UP_FOR =>
+            {
+                heads_up.clone()
+            }
+            _ => title.to_string(),
+        }
+    }
+
+    /// What the clock box says during the current phase - its message in the sequence, if it
+    /// has one, or else `default`.
+    fn label(&self, default: &str) -> String {
+        let message = self
+            .engine
+            .tracker()
+            .phase()
+            .and_then(|phase| phase.message.as_deref());
+        self.title(message.unwrap_or(default))
+    }
+
+    /*
+     * CLOCK AND DRAWING METHODS
+     */
+
+    /// Draws the work, break or paused screen, depending on where the engine is at.
+    pub fn draw_screen(&mut self) -> Result<(), PomodoroError> {
+        match self.engine.state() {
+            PomodoroState::None => Ok(()),
+            _ if self.engine.is_paused() => self.draw_paused_screen(),
+            _ if self.engine.is_overtime() => self.draw_overtime_screen(),
+            PomodoroState::Working => self.draw_work_screen(),
+            PomodoroState::ShortBreak | PomodoroState::LongBreak => self.draw_break_screen(),
+        }
+    }
+
+    /// Whether the terminal is too small for the full clock.
+    pub fn is_compact(&self) -> bool {
+        self.width < MIN_WIDTH || self.height < MIN_HEIGHT
+    }
+
+    /// Draws the work clock on the screen - counting up, with a reminder of how to stop it, in a
+    /// Flowtime work period.
+    pub fn draw_work_screen(&mut self) -> Result<(), PomodoroError> {
+        let title = match self.engine.is_flowing() {
+            true => self.title("In the flow - n for a break"),
+            false => self.label("Time to Work!"),
+        };
+        if self.is_compact() {
+            return self.draw_compact_clock(&title);
+        }
+
+        let clock = self.engine.clock().gen_clock(&title);
+        self.draw_work_count()?;
+        self.draw_controls_help()?;
+        self.draw_clock(clock)?;
+        self.draw_warning()
+    }
+
+    /// Draws the break clock on the screen.
+    pub fn draw_break_screen(&mut self) -> Result<(), PomodoroError> {
+        if self.is_compact() {
+            return self.draw_compact_clock(&self.label("Time to Chill"));
+        }
+
+        let clock = self.engine.clock().gen_clock(&self.label("Time to Chill"));
+        self.draw_work_count()?;
+        self.draw_clock(clock)?;
+        self.draw_controls_help()?;
+        self.draw_warning()
+    }
+
+    /// Draws the clock counting up, in red, once the phase has run down and is running over.
+    ///
+    /// # Example:
+    ///
+    /// "+03:12 over"
+    pub fn draw_overtime_screen(&mut self) -> Result<(), PomodoroError> {
+        let title = match self.engine.state() {
+            PomodoroState::Working => "Time for a break!",
+            _ => "Break's over!",
+        };
+        if self.is_compact() {
+            return self.draw_compact_clock(title);
+        }
+
+        let clock = self.engine.clock().gen_overtime_clock(title);
+        self.draw_work_count()?;
+        write!(self.stdout, "{}", color::Fg(color::Red))?;
+        self.draw_clock(clock)?;
+        write!(self.stdout, "{}", color::Fg(color::Reset))?;
+        self.draw_centered(OVERTIME_HELP, Some(8))?;
+        self.draw_warning()
+    }
+
+    /// Draws the frozen clock while the countdown is paused.
+    pub fn draw_paused_screen(&mut self) -> Result<(), PomodoroError> {
+        if self.is_compact() {
+            return self.draw_compact_clock("Paused");
+        }
+
+        let clock = self.engine.clock().gen_clock("Paused");
+        self.draw_work_count()?;
+        self.draw_clock(clock)?;
+        self.draw_controls_help()?;
+        self.draw_warning()
+    }
+
+    /// Draws the countdown to the next phase starting on its own.
+    ///
+    /// # Example:
+    ///
+    /// "Next pomodoro starts in 10s"
+    pub fn draw_grace_screen(
+        &mut self,
+        next: PomodoroState,
+        remaining: Duration,
+    ) -> Result<(), PomodoroError> {
+        let next = match next {
+            PomodoroState::Working => "Next pomodoro",
+            _ => "Your break",
+        };
+        let lines = format!(
+            "{} starts in {}s\npress any key to hold",
+            next,
+            remaining.as_secs()
+        );
+        self.draw_centered(&lines, None)?;
+        self.draw_warning()
+    }
+
+    /// Draws the clock, the message and the work count on a single line, for terminals too small
+    /// for the full clock.
+    ///
+    /// # Example:
+    ///
+    /// "Time to Work! 24:40 (1 of 4)"
+    pub fn draw_compact_clock(&mut self, message: &str) -> Result<(), PomodoroError> {
+        let mut time = self.engine.clock().get_time();
+        if self.engine.is_overtime() {
+            time = format!("+{} over", time);
+        }
+        let line = format!(
+            "{} {} ({} of {})",
+            message,
+            time,
+            self.engine.order(),
+            self.engine.cycle_length(),
+        );
+        self.draw_centered(&line, None)?;
+        self.draw_warning()
+    }
+
+    /// Takes in an input string and prints it centered on the screen
+    /// Returns the last line it was printed on in case another method
+    /// needs to know that to clear space after it.  Anything that doesn't fit is cut off at the
+    /// edge of the terminal rather than wrapped.
+    pub fn draw_centered(
+        &mut self,
+        item: &str,
+        height_offset: Option<u16>,
+    ) -> Result<usize, PomodoroError> {
+        let lines = item.lines();
+        let line_vec = item.lines().collect::<Vec<_>>();
+
+        let h = line_vec.len() as u16;
+        let w = line_vec.iter().map(|line| line.chars().count()).max();
+        let w = w.unwrap_or_default() as u16;
+
+        let height_offset = height_offset.unwrap_or_default();
+        let x = ((self.width / 2).saturating_sub(w / 2)).max(1);
+        let top = ((self.height / 2).saturating_sub(h / 2)).max(1) + height_offset;
+
+        let mut last_i = 0;
+        for (i, line) in lines.enumerate() {
+            let y = top + i as u16;
+            if y > self.height {
+                break;
+            }
+
+            let visible = (self.width + 1).saturating_sub(x) as usize;
+            write!(
+                self.stdout,
+                "{}{}{}",
+                cursor::Goto(x, y),
+                clear::CurrentLine,
+                line.chars().take(visible).collect::<String>(),
+            )?;
+
+            last_i = i;
+        }
+
+        Ok(last_i)
+    }
+
+    /// Draws the clock on the screen.
+    pub fn draw_clock(&mut self, clock: String) -> Result<(), PomodoroError> {
+        self.draw_centered(&clock, None)?;
+        Ok(())
+    }
+
+    /// Draws the current work count on the screen, along with the name of the phase if the
+    /// sequence gives it one.
+    ///
+    /// # Example:
+    ///
+    /// "Work Period 1 of 4", or "Deep work (1 of 3)"
+    pub fn draw_work_count(&mut self) -> Result<(), PomodoroError> {
+        let (order, cycle_length) = (self.engine.order(), self.engine.cycle_length());
+        let work_count = match self.engine.tracker().phase().and_then(|p| p.name.as_ref()) {
+            Some(name) => format!("{} ({} of {})", name, order, cycle_length),
+            None => format!("Work Period {} of {}", order, cycle_length),
+        };
+        let w = work_count.chars().count() as u16;
+
+        write!(
+            self.stdout,
+            "\r\n{}{}{}",
+            cursor::Goto(
+                ((self.width / 2).saturating_sub(w / 2)).max(1),
+                (self.height / 2) + 5
+            ),
+            clear::CurrentLine,
+            work_count,
+        )?;
+        Ok(())
+    }
+
+    /// Draws the section of the screen when the work and break clocks are rolling that actively
+    /// remind user of async commands they can issue at any time to restart or quit the current
+    /// cycle.
+    pub fn draw_controls_help(&mut self) -> Result<(), PomodoroError> {
+        self.draw_centered(CONTROLS, Some(8))?;
+        Ok(())
+    }
+
+    /// Draws the current warning (if there is one) along the bottom line of the terminal.
+    pub fn draw_warning(&mut self) -> Result<(), PomodoroError> {
+        if let Some(warning) = &self.warning {
+            write!(
+                self.stdout,
+                "{}{}warning: {}",
+                cursor::Goto(1, self.height.max(1)),
+                clear::CurrentLine,
+                warning
+                    .chars()
+                    .take((self.width as usize).saturating_sub(9))
+                    .collect::<String>(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn clear_lines(&mut self, lines: &[usize; 2]) -> Result<(), PomodoroError> {
+        for i in lines[0]..lines[1] {
+            let y = ((self.height / 2).saturating_sub(3) + i as u16).max(1);
+            if y > self.height {
+                break;
+            }
+
+            write!(self.stdout, "{}{}", cursor::Goto(1, y), clear::CurrentLine,)?;
+        }
+        Ok(())
+    }
+
+    /// Displays the pomodoro menu that is seen either at the start (large menu) or between
+    /// pomodoros (small menu, or the break menu if the break is still to be started)
+    pub fn display_menu(&mut self, menu: Option<&'static str>) -> Result<(), PomodoroError> {
+        if self.exiting {
+            return Ok(());
+        }
+
+        let menu = if let Some(menu) = menu {
+            menu
+        } else if self.engine.is_break_next() {
+            BREAK_MENU
+        } else {
+            POMODORO_MENU
+        };
+        self.menu = Some(menu);
+
+        write!(self.stdout, "{}", clear::All)?;
+        self.draw_menu(menu)?;
+        self.stdout.flush()?;
+
+        match self.wait_for_next_command()? {
+            Command::Start => self.begin_cycle(),
+            Command::Extend | Command::Snooze => {
+                self.snooze_phase()?;
+                self.countdown()?;
+                self.display_menu(None)
+            }
+            Command::Quit
+            | Command::Reset
+            | Command::Pause
+            | Command::Skip
+            | Command::Acknowledge
+            | Command::Ambience
+            | Command::Hold
+            | Command::None => Ok(()),
+        }
+    }
+
+    fn draw_menu(&mut self, menu: &str) -> Result<(), PomodoroError> {
+        if self.is_compact() {
+            self.draw_centered(COMPACT_MENU, None)?;
+        } else {
+            let last_i = self.draw_centered(menu, None)?;
+            self.clear_lines(&[last_i + 1, last_i + 8])?;
+        }
+        self.draw_warning()
+    }
+
+    /// WAITS (in a loop) for the next user command (happens between pomodoros).
+    pub fn wait_for_next_command(&mut self) -> Result<Command, PomodoroError> {
+        let mut command = Command::None;
+
+        while let Command::None = command {
+            self.check_resize()?;
+
+            let mut buf = [0];
+            if self.stdin.read(&mut buf)? == 0 {
+                command = self.poll_background()?;
+                if let Command::None = command {
+                    self.time.sleep(INPUT_POLL);
+                }
+                continue;
+            }
+            command = match buf[0] {
+                b's' => Command::Start,
+                // only the break menu offers more time
+                b'+' if self.menu == Some(BREAK_MENU) => Command::Extend,
+                b'r' => Command::Reset,
+                b'q' => Command::Quit,
+                _ => continue,
+            }
+        }
+
+        Ok(command)
+    }
+
+    /// listens for the next command while clock is counting down in a non-blocking (async)
+    /// fashion.
+    pub fn async_command_listen(&mut self) -> Result<Command, PomodoroError> {
+        let mut buf = [0];
+        if self.stdin.read(&mut buf)? == 0 {
+            return Ok(Command::None);
+        }
+        Ok(match buf[0] {
+            b'r' => Command::Reset,
+            b'q' => Command::Quit,
+            b'p' => Command::Pause,
+            b'n' => Command::Skip,
+            b'+' => Command::Extend,
+            b't' => Command::Ambience,
+            _ => Command::None,
+        })
+    }
+
+    /// Listens for a keypress while the phase is running over - `q` and `+` do what they always
+    /// do, and any other key moves on.
+    pub fn overtime_listen(&mut self) -> Result<Command, PomodoroError> {
+        let mut buf = [0];
+        if self.stdin.read(&mut buf)? == 0 {
+            return Ok(Command::None);
+        }
+        Ok(match buf[0] {
+            b'q' => Command::Quit,
+            b'+' => Command::Extend,
+            _ => Command::Acknowledge,
+        })
+    }
+
+    /// Listens for a keypress while counting down to the next phase starting on its own - `+`
+    /// snoozes the phase that has just run down, and any other key holds the next one.
+    pub fn grace_listen(&mut self) -> Result<Command, PomodoroError> {
+        let mut buf = [0];
+        if self.stdin.read(&mut buf)? == 0 {
+            return Ok(Command::None);
+        }
+        Ok(match buf[0] {
+            b'+' => Command::Extend,
+            _ => Command::Hold,
+        })
+    }
+}
+
+/// Simple struct to translate user keystrokes into command types we can enforce with matches.
+pub enum Command {
+    Start,
+    Reset,
+    Quit,
+    Pause,
+    Skip,
+    Extend,
+    Snooze,
+    Acknowledge,
+    Ambience,
+    Hold,
+    None,
+}
+
+/// Wraps the terminal the session draws to, and puts the screen back the way we found it when it
+/// is dropped - whether the session finished, bailed out with an error or panicked.  The raw mode
+/// terminal inside it restores the terminal settings once we're done.
+struct TerminalGuard<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> Write for TerminalGuard<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for TerminalGuard<W> {
+    fn drop(&mut self) {
+        // Leave the panic message on screen rather than clearing it away.
+        if !thread::panicking() {
+            let _ = write!(self.inner, "{}{}", clear::All, cursor::Goto(1, 1));
+        }
+        let _ = write!(self.inner, "{}{}\r\n", style::Reset, cursor::Show);
+        let _ = self.inner.flush();
+    }
+}
+
+/// Initializes the pomodoro session (this is run from start to finish)
+pub fn init(
+    width: u16,
+    height: u16,
+    config: PomodoroConfig,
+    history: History,
+) -> Result<(), PomodoroError> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock().into_raw_mode()?;
+    let mut stdout = TerminalGuard { inner: stdout };
+    let stdin = termion::async_stdin();
+
+    write!(stdout, "{}{}", clear::All, cursor::Goto(1, 1))?;
+    stdout.flush()?;
+
+    let mut pomodoro_screen = PomodoroSession::new(stdin, stdout, width, height, &config);
+    pomodoro_screen.record_to(history);
+
+    let resized = Arc::new(AtomicBool::new(false));
+    if signal_hook::flag::register(signal_hook::consts::SIGWINCH, Arc::clone(&resized)).is_ok() {
+        pomodoro_screen.watch_resize(resized);
+    }
+
+    match control::socket_path().and_then(ControlServer::bind) {
+        Ok(control) => pomodoro_screen.listen_on(control),
+        Err(e) => pomodoro_screen.warn(format!("remote control is off: {}", e)),
+    }
+
+    pomodoro_screen.start()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigFile, Warnings};
+    use crate::notify::Recorder;
+    use crate::time::ManualClock;
+    use crate::PomodoroOptions;
+    use std::path::PathBuf;
+
+    /// Keyboard input that only shows up once the fake clock reaches each key's time.
+    struct ScriptedInput {
+        time: ManualClock,
+        keys: Vec<(Duration, u8)>,
+    }
+
+    impl Read for ScriptedInput {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.keys.first() {
+                Some(&(at, key)) if self.time.elapsed() >= at => {
+                    self.keys.remove(0);
+                    buf[0] = key;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    /// A session on a fake clock that gets `keys` typed at it, recording its notifications and
+    /// its history to `history.jsonl` in a directory of its own.  Remove the directory once the
+    /// test is done.
+    fn session(
+        name: &str,
+        config: &PomodoroConfig,
+        keys: Vec<(Duration, u8)>,
+    ) -> (
+        PomodoroSession<ScriptedInput, Vec<u8>, ManualClock>,
+        ManualClock,
+        Recorder,
+        PathBuf,
+    ) {
+        let dir =
+            std::env::temp_dir().join(format!("pomodoro-tui-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let time = ManualClock::new();
+        let stdin = ScriptedInput {
+            time: time.clone(),
+            keys,
+        };
+        let mut session =
+            PomodoroSession::with_time_source(stdin, vec![], 80, 30, config, time.clone());
+        let recorder = Recorder::new();
+        session.notify_with(recorder.clone());
+        session.record_to(History::at(dir.join("history.jsonl")));
+        (session, time, recorder, dir)
+    }
+
+    #[test]
+    fn test_pause_in_session() {
+        let keys = vec![
+            (Duration::from_secs(10), b'p'),
+            (Duration::from_secs(70), b'p'),
+            (Duration::from_millis(80_500), b'q'),
+        ];
+        let (mut session, time, recorder, dir) = session("pause", &PomodoroConfig::default(), keys);
+        session.start_work().unwrap();
+
+        assert!(!session.engine().is_running());
+        assert_eq!(session.engine().clock().get_time(), "24:40");
+        assert!(time.elapsed() < Duration::from_secs(81));
+
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert!(screen.contains("Paused"));
+        assert!(screen.contains("Work Period 1 of 4"));
+
+        let entries = History::at(dir.join("history.jsonl")).entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].outcome, Outcome::Quit);
+        assert_eq!(entries[0].actual_secs, 20);
+        assert_eq!(entries[0].paused_secs, 60);
+        assert!(recorder.messages().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_skip_work_and_break() {
+        let keys = vec![
+            (Duration::from_secs(10), b'n'),
+            (Duration::from_secs(20), b'n'),
+        ];
+        let (mut session, time, recorder, dir) = session("skip", &PomodoroConfig::default(), keys);
+        session.start_work().unwrap();
+        assert!(!session.engine().is_running());
+        assert!(time.elapsed() < Duration::from_secs(21));
+        assert!(String::from_utf8_lossy(&session.stdout).contains("Time to Chill"));
+
+        let entries = History::at(dir.join("history.jsonl")).entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].phase, PomodoroState::Working);
+        assert_eq!(entries[0].outcome, Outcome::Skipped);
+        assert_eq!(entries[0].actual_secs, 10);
+        assert_eq!(entries[1].phase, PomodoroState::ShortBreak);
+        assert_eq!(entries[1].outcome, Outcome::Skipped);
+
+        // nothing ran down, so there was nothing to say
+        assert!(recorder.messages().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_five_more_minutes() {
+        let config = PomodoroConfig {
+            work_time: 1,
+            short_break_time: 1,
+            ..PomodoroConfig::default()
+        };
+        let keys = vec![
+            (Duration::from_secs(10), b'+'),
+            (Duration::from_secs(370), b'+'),
+        ];
+        let (mut session, time, recorder, dir) = session("extend", &config, keys);
+
+        // the first + stretches the work period, the second brings it back once it has run down
+        session.start_work().unwrap();
+        assert!(!session.engine().is_running());
+        assert!(time.elapsed() < Duration::from_secs(731));
+        assert!(String::from_utf8_lossy(&session.stdout).contains("+5 minutes"));
+
+        let entries = History::at(dir.join("history.jsonl")).entries().unwrap();
+        let outcomes = entries
+            .iter()
+            .map(|entry| {
+                let times = (entry.planned_secs, entry.extended_secs);
+                (entry.phase, times, entry.snoozed, entry.outcome)
+            })
+            .collect::<Vec<_>>();
+        {
+            use Outcome::*;
+            use PomodoroState::*;
+            assert_eq!(
+                outcomes,
+                vec![
+                    (Working, (60, 300), false, Completed),
+                    (ShortBreak, (60, 0), false, Skipped),
+                    (Working, (300, 0), true, Completed),
+                    (ShortBreak, (60, 0), false, Completed),
+                ]
+            );
+        }
+
+        let summaries = recorder
+            .messages()
+            .into_iter()
+            .map(|message| message.summary)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summaries,
+            vec!["Pomodoro Break!", "Pomodoro Break!", "Pomodoro Break Over"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_more_time_only_from_the_break_menu() {
+        let keys = vec![
+            (Duration::from_secs(0), b'+'),
+            (Duration::from_secs(1), b'q'),
+        ];
+        let (mut session, time, recorder, dir) = session("menu", &PomodoroConfig::default(), keys);
+
+        // the welcome menu has nothing to give more time to, so + leaves it up as it is
+        session.display_menu(Some(POMODORO_START_PROMPT)).unwrap();
+        assert!(time.elapsed() >= Duration::from_secs(1));
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert_eq!(screen.matches("Start your first Pomodoro!").count(), 1);
+        assert!(!screen.contains("start next"));
+        assert!(recorder.messages().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_overtime_until_a_key_is_pressed() {
+        let config = PomodoroConfig {
+            work_time: 1,
+            overtime: true,
+            ..PomodoroConfig::default()
+        };
+        let keys = vec![
+            (Duration::from_secs(200), b'x'),
+            (Duration::from_secs(530), b'x'),
+        ];
+        let (mut session, time, recorder, dir) = session("overtime", &config, keys);
+
+        session.start_work().unwrap();
+        assert!(!session.engine().is_running());
+        assert!(time.elapsed() < Duration::from_secs(531));
+
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert!(screen.contains("+02:19 over"));
+        assert!(screen.contains("+00:29 over"));
+
+        let summaries = recorder
+            .messages()
+            .into_iter()
+            .map(|message| message.summary)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summaries,
+            vec![
+                "Pomodoro Break!",
+                "1 minute over",
+                "2 minutes over",
+                "Pomodoro Break Over",
+            ]
+        );
+
+        let entries = History::at(dir.join("history.jsonl")).entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].actual_secs, 60);
+        assert_eq!(entries[0].overtime_secs, 140);
+        assert_eq!(entries[1].overtime_secs, 30);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_hold_the_next_pomodoro() {
+        let config = PomodoroConfig {
+            work_time: 1,
+            short_break_time: 1,
+            auto_start_work: true,
+            grace: 5,
+            ..PomodoroConfig::default()
+        };
+        let keys = vec![(Duration::from_secs(127), b'x')];
+        let (mut session, time, recorder, dir) = session("hold", &config, keys);
+
+        // work and the break roll on by themselves, then the key holds the next pomodoro
+        session.start_work().unwrap();
+        assert!(!session.engine().is_running());
+        assert_eq!(session.engine().starts_at(), None);
+        assert!(time.elapsed() < Duration::from_secs(131));
+
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert!(screen.contains("Time to Chill"));
+        assert!(screen.contains("Your break starts in 5s"));
+        assert!(screen.contains("Next pomodoro starts in 5s"));
+        assert!(screen.contains("Next pomodoro starts in 4s"));
+        assert!(screen.contains("press any key to hold"));
+        assert!(!screen.contains("Next pomodoro starts in 3s"));
+
+        // the countdown is redrawn in place, without clearing the screen every second
+        let from = screen.find("Next pomodoro starts in 5s").unwrap();
+        let to = screen.find("Next pomodoro starts in 4s").unwrap();
+        assert!(!screen[from..to].contains(&clear::All.to_string()));
+
+        let summaries = recorder
+            .messages()
+            .into_iter()
+            .map(|message| message.summary)
+            .collect::<Vec<_>>();
+        assert_eq!(summaries, vec!["Pomodoro Break!", "Pomodoro Break Over"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resize_repaints() {
+        let time = ManualClock::new();
+        let config = PomodoroConfig::default();
+        let mut session =
+            PomodoroSession::with_time_source(io::empty(), vec![], 80, 30, &config, time.clone());
+
+        let events = session.engine.start_work(time.now());
+        session.handle_events(events).unwrap();
+        assert!(!session.is_compact());
+
+        session.stdout.clear();
+        session.resize(30, 10).unwrap();
+        assert!(session.is_compact());
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert!(screen.contains("Time to Work! 25:00 (1 of 4)"));
+        assert!(!screen.contains("Work Period"));
+
+        session.resize(1, 1).unwrap();
+
+        session.stdout.clear();
+        session.resize(100, 40).unwrap();
+        assert_eq!(session.size(), (100, 40));
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert!(screen.contains("Work Period 1 of 4"));
+        assert!(screen.contains("controls"));
+    }
+
+    #[test]
+    fn test_sequence_labels() {
+        let time = ManualClock::new();
+        let file = ConfigFile::parse(
+            r#"
+sequence = [
+    { kind = "work", duration = 90, name = "Deep work", message = "Heads down" },
+    { kind = "long_break", duration = 30 },
+]
+"#,
+        )
+        .unwrap();
+        let config = PomodoroConfig::resolve(&file, &PomodoroOptions::default()).unwrap();
+        let mut session =
+            PomodoroSession::with_time_source(io::empty(), vec![], 80, 30, &config, time.clone());
+
+        let events = session.engine.start_work(time.now());
+        session.handle_events(events).unwrap();
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert!(screen.contains("Heads down"));
+        assert!(screen.contains("1:30:00"));
+        assert!(screen.contains("Deep work (1 of 1)"));
+
+        session.stdout.clear();
+        let events = session.engine.skip(time.now());
+        session.handle_events(events).unwrap();
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert!(screen.contains("Time to Chill"));
+        assert!(screen.contains("Work Period 1 of 1"));
+    }
+
+    #[test]
+    fn test_notifies_at_phase_end() {
+        let time = ManualClock::new();
+        let config = PomodoroConfig::default();
+        let mut session =
+            PomodoroSession::with_time_source(io::empty(), vec![], 80, 30, &config, time.clone());
+        let recorder = Recorder::new();
+        session.notify_with(recorder.clone());
+
+        let start = time.now();
+        let events = session.engine.start_work(start);
+        session.handle_events(events).unwrap();
+        assert!(recorder.messages().is_empty());
+
+        let events = session.engine.tick(start + Duration::from_secs(25 * 60));
+        session.handle_events(events).unwrap();
+        assert_eq!(
+            recorder.messages(),
+            vec![Message::phase_end(PomodoroState::Working)]
+        );
+        assert_eq!(session.warning(), None);
+    }
+
+    #[test]
+    fn test_warning_before_the_end() {
+        let time = ManualClock::new();
+        let config = PomodoroConfig {
+            warnings: Warnings {
+                work: vec![2],
+                breaks: vec![],
+            },
+            ..PomodoroConfig::default()
+        };
+        let mut session =
+            PomodoroSession::with_time_source(io::empty(), vec![], 80, 30, &config, time.clone());
+        let recorder = Recorder::new();
+        session.notify_with(recorder.clone());
+
+        let start = time.now();
+        let events = session.engine.start_work(start);
+        session.handle_events(events).unwrap();
+
+        time.sleep(Duration::from_secs(23 * 60));
+        let events = session.engine.tick(time.now());
+        session.handle_events(events).unwrap();
+        assert_eq!(
+            recorder.messages(),
+            vec![Message::warning(
+                PomodoroState::Working,
+                Duration::from_secs(120)
+            )]
+        );
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert!(screen.contains("2 minutes left"));
+
+        // the heads up only stays in the clock box for a few seconds
+        session.stdout.clear();
+        time.sleep(HEADS_UP_FOR);
+        let events = session.engine.tick(time.now());
+        session.handle_events(events).unwrap();
+        let screen = String::from_utf8_lossy(&session.stdout);
+        assert!(screen.contains("Time to Work!"));
+        assert!(!screen.contains("minutes left"));
+    }
+
+    #[test]
+    fn test_control_socket() {
+        let (mut session, time, recorder, dir) =
+            session("control", &PomodoroConfig::default(), vec![]);
+        let path = dir.join("control.sock");
+        session.listen_on(ControlServer::bind(&path).unwrap());
+
+        let mut ask = |request| {
+            let client_path = path.clone();
+            let client = std::thread::spawn(move || control::send(&client_path, request).unwrap());
+            let mut command = Command::None;
+            while !client.is_finished() {
+                if let Command::None = command {
+                    command = session.poll_control().unwrap();
+                }
+            }
+            (command, client.join().unwrap())
+        };
+
+        let (_, response) = ask(Request::Pause);
+        assert_eq!(response.error.as_deref(), Some("no pomodoro is running"));
+
+        let config = PomodoroConfig {
+            work_time: 50,
+            ..PomodoroConfig::default()
+        };
+        let (command, response) = ask(Request::Start {
+            config: Some(Box::new(config)),
+        });
+        assert!(matches!(command, Command::Start));
+        let status = response.status.unwrap();
+        assert_eq!(status.state, PomodoroState::Working);
+        assert_eq!(status.remaining, "50:00");
+
+        time.advance(Duration::from_secs(20));
+        let (_, response) = ask(Request::Pause);
+        let status = response.status.unwrap();
+        assert!(response.ok);
+        assert!(status.paused);
+        assert_eq!(status.order, 1);
+
+        let (_, response) = ask(Request::Skip);
+        assert_eq!(response.status.unwrap().state, PomodoroState::ShortBreak);
+
+        let (command, response) = ask(Request::Quit);
+        assert!(matches!(command, Command::Quit));
+        assert_eq!(response.status.unwrap().state, PomodoroState::None);
+        assert!(session.exiting);
+        assert!(recorder.messages().is_empty());
+
+        drop(session);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A terminal that has gone away.
+    struct ClosedTerminal;
+
+    impl Write for ClosedTerminal {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "terminal closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_errors_end_the_session() {
+        let time = ManualClock::new();
+        let config = PomodoroConfig::default();
+        let mut session =
+            PomodoroSession::with_time_source(io::empty(), ClosedTerminal, 80, 30, &config, time);
+
+        match session.start_work() {
+            Err(PomodoroError::Terminal(e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+            _ => panic!("expected a terminal error"),
+        }
+    }
+
+    #[test]
+    fn test_guard_restores_terminal() {
+        let mut screen = vec![];
+        {
+            let mut guard = TerminalGuard { inner: &mut screen };
+            write!(guard, "{}", cursor::Hide).unwrap();
+        }
+
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.ends_with(&format!("{}\r\n", cursor::Show)));
+    }
+}
@@ -0,0 +1,96 @@
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// The directory pomodoro keeps its data in - `$XDG_DATA_HOME/pomodoro`, or
+/// `~/.local/share/pomodoro` when that isn't set.
+pub fn data_dir() -> Option<PathBuf> {
+    base_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join("pomodoro"))
+}
+
+/// The directory pomodoro reads its config from - `$XDG_CONFIG_HOME/pomodoro`, or
+/// `~/.config/pomodoro` when that isn't set.
+pub fn config_dir() -> Option<PathBuf> {
+    base_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("pomodoro"))
+}
+
+/// The directory for sockets and other files that only live as long as the login session -
+/// `$XDG_RUNTIME_DIR`, or a per-user directory under the system temp dir when that isn't set.
+/// Anyone can create that one, so it is made private to you, and refused if somebody else got
+/// there first.
+pub fn runtime_dir() -> io::Result<PathBuf> {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => {
+            let uid = unsafe { libc::getuid() };
+            let dir = env::temp_dir().join(format!("pomodoro-{}", uid));
+            private_dir(&dir, uid)?;
+            Ok(dir)
+        }
+    }
+}
+
+/// Creates `dir` readable by `uid` alone, or checks that the one already there is.
+fn private_dir(dir: &Path, uid: u32) -> io::Result<()> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+        Err(e) => return Err(e),
+    }
+
+    // not followed if it is a link, so it can't point somewhere else
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.permissions().mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{} isn't a private directory of yours - remove it or set $XDG_RUNTIME_DIR",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Looks up an XDG base directory, falling back to `fallback` under the home directory.
+fn base_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    match env::var_os(var) {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => Some(PathBuf::from(env::var_os("HOME")?).join(fallback)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use std::process;
+
+    #[test]
+    fn test_private_dir() {
+        let base = env::temp_dir().join(format!("pomodoro-xdg-{}", process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        let uid = unsafe { libc::getuid() };
+
+        let dir = base.join("runtime");
+        private_dir(&dir, uid).unwrap();
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        private_dir(&dir, uid).unwrap();
+
+        // somebody else's directory, one others can get into, or a link are all turned down
+        assert!(private_dir(&dir, uid + 1).is_err());
+        let shared = base.join("shared");
+        DirBuilder::new().mode(0o755).create(&shared).unwrap();
+        assert!(private_dir(&shared, uid).is_err());
+        let link = base.join("link");
+        symlink(&dir, &link).unwrap();
+        let error = private_dir(&link, uid).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}